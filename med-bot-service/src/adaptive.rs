@@ -0,0 +1,196 @@
+use chrono::NaiveDate;
+use std::time::Duration;
+use crate::config::scheduler_config::SchedulerConfig;
+use crate::models::doctor_appointment::AppointmentWindow;
+use crate::models::documents::Doctor;
+
+/// Days before the window start from which the interval starts shrinking towards the minimum
+const HORIZON_DAYS: i64 = 14;
+/// Upstream requests made by one target check: search + appointments
+const REQUESTS_PER_CHECK: u64 = 2;
+const MIN_CHURN_FACTOR: f64 = 0.25;
+const MAX_CHURN_FACTOR: f64 = 2.0;
+
+/// Per target polling state, carried across checks
+#[derive(Debug, Clone)]
+pub struct AdaptiveState {
+    /// Below 1 after recent slot changes, drifts above 1 while nothing changes
+    churn_factor: f64,
+}
+
+impl Default for AdaptiveState {
+    fn default() -> Self {
+        AdaptiveState { churn_factor: 1.0 }
+    }
+}
+
+impl AdaptiveState {
+    pub fn record_check(&mut self, changed: bool) {
+        self.churn_factor = if changed {
+            (self.churn_factor / 2.0).max(MIN_CHURN_FACTOR)
+        } else {
+            (self.churn_factor * 1.25).min(MAX_CHURN_FACTOR)
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AdaptivePolicy {
+    min_interval: Duration,
+    max_interval: Duration,
+    requests_per_hour: u32,
+}
+
+impl AdaptivePolicy {
+    pub fn new(scheduler_config: &SchedulerConfig) -> AdaptivePolicy {
+        AdaptivePolicy {
+            min_interval: scheduler_config.min_interval,
+            max_interval: scheduler_config.max_interval,
+            requests_per_hour: scheduler_config.requests_per_hour,
+        }
+    }
+
+    /// Delay until the next check of `doctor`, a window that already ended is checked at
+    /// `max_interval`. The request budget wins over `max_interval` when there are too many
+    /// targets to fit in it.
+    pub fn interval(&self, doctor: &Doctor, state: &AdaptiveState, today: NaiveDate, target_count: usize) -> Duration {
+        let interval = match AppointmentWindow::from_doctor(doctor, today) {
+            Ok(window) if window.date_to < today => self.max_interval,
+            _ => self.proximity_interval(doctor, today)
+                .mul_f64(state.churn_factor)
+                .clamp(self.min_interval, self.max_interval),
+        };
+        interval.max(self.budget_interval(target_count))
+    }
+
+    /// Linear from `max_interval` at the horizon down to `min_interval` once the window has started
+    fn proximity_interval(&self, doctor: &Doctor, today: NaiveDate) -> Duration {
+        let days_left = match AppointmentWindow::from_doctor(doctor, today) {
+            // A rolling window has no fixed start to get closer to
+            Ok(_) if doctor.date_from.is_none() && doctor.target_date.is_none() => HORIZON_DAYS / 2,
+            Ok(window) => (window.date_from - today).num_days().clamp(0, HORIZON_DAYS),
+            Err(_) => HORIZON_DAYS,
+        };
+
+        let span = self.max_interval.saturating_sub(self.min_interval);
+        self.min_interval + span.mul_f64(days_left as f64 / HORIZON_DAYS as f64)
+    }
+
+    /// Shortest interval that keeps `target_count` targets, polled adaptively or by cron, within
+    /// the hourly request budget
+    fn budget_interval(&self, target_count: usize) -> Duration {
+        let requests = target_count as u64 * REQUESTS_PER_CHECK;
+        Duration::from_secs(3600 * requests / self.requests_per_hour as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MIN: Duration = Duration::from_secs(60);
+    const MAX: Duration = Duration::from_secs(3600);
+
+    fn policy() -> AdaptivePolicy {
+        AdaptivePolicy {
+            min_interval: MIN,
+            max_interval: MAX,
+            requests_per_hour: 600,
+        }
+    }
+
+    fn doctor(window: serde_json::Value) -> Doctor {
+        let mut document = json!({
+            "doctor_ref_id": "doctor-001",
+            "doctor_name": "Nguyen Van A",
+            "subject_ref_id": "subject-001",
+            "subject_name": "Tai Mui Hong",
+            "service_name": "Kham dich vu",
+            "hospital_id": "partner-001",
+            "city_id": "city-hcm",
+            "current_target": true,
+            "active": true,
+        });
+        document.as_object_mut().unwrap().extend(window.as_object().unwrap().clone());
+        serde_json::from_value(document).unwrap()
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 10, 1).unwrap()
+    }
+
+    #[test]
+    fn far_window_polls_at_max_interval() {
+        let doctor = doctor(json!({"date_from": "2024-11-01", "date_to": "2024-11-07"}));
+
+        assert_eq!(policy().interval(&doctor, &AdaptiveState::default(), today(), 1), MAX);
+    }
+
+    #[test]
+    fn started_window_polls_at_min_interval() {
+        let doctor = doctor(json!({"date_from": "2024-09-28", "date_to": "2024-10-07"}));
+
+        assert_eq!(policy().interval(&doctor, &AdaptiveState::default(), today(), 1), MIN);
+    }
+
+    #[test]
+    fn interval_shrinks_linearly_towards_the_window() {
+        let doctor = doctor(json!({"date_from": "2024-10-08", "date_to": "2024-10-14"}));
+
+        // Half the horizon left
+        assert_eq!(policy().interval(&doctor, &AdaptiveState::default(), today(), 1), Duration::from_secs(1830));
+    }
+
+    #[test]
+    fn rolling_window_sits_halfway() {
+        let doctor = doctor(json!({"window_days": 14}));
+
+        assert_eq!(policy().interval(&doctor, &AdaptiveState::default(), today(), 1), Duration::from_secs(1830));
+    }
+
+    #[test]
+    fn ended_window_backs_off_to_max_interval() {
+        let doctor = doctor(json!({"date_from": "2024-09-20", "date_to": "2024-09-30"}));
+        let mut state = AdaptiveState::default();
+        state.record_check(true);
+        state.record_check(true);
+
+        assert_eq!(policy().interval(&doctor, &state, today(), 1), MAX);
+    }
+
+    #[test]
+    fn invalid_window_polls_at_max_interval() {
+        let doctor = doctor(json!({}));
+
+        assert_eq!(policy().interval(&doctor, &AdaptiveState::default(), today(), 1), MAX);
+    }
+
+    #[test]
+    fn churn_speeds_up_and_quiet_slows_down_within_bounds() {
+        let doctor = doctor(json!({"date_from": "2024-10-08", "date_to": "2024-10-14"}));
+        let mut state = AdaptiveState::default();
+
+        state.record_check(true);
+        assert_eq!(policy().interval(&doctor, &state, today(), 1), Duration::from_secs(915));
+        for _ in 0..10 {
+            state.record_check(true);
+        }
+        assert_eq!(state.churn_factor, MIN_CHURN_FACTOR);
+
+        for _ in 0..10 {
+            state.record_check(false);
+        }
+        assert_eq!(state.churn_factor, MAX_CHURN_FACTOR);
+        assert_eq!(policy().interval(&doctor, &state, today(), 1), MAX);
+    }
+
+    #[test]
+    fn request_budget_wins_over_max_interval() {
+        let doctor = doctor(json!({"date_from": "2024-09-28", "date_to": "2024-10-07"}));
+
+        // 400 targets make 800 requests per check round, 600 fit in an hour
+        assert_eq!(policy().interval(&doctor, &AdaptiveState::default(), today(), 400), Duration::from_secs(4800));
+        assert_eq!(policy().budget_interval(30), Duration::from_secs(360));
+    }
+}
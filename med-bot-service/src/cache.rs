@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// In-memory cache whose entries expire `ttl` after being stored. A zero `ttl` disables it.
+#[derive(Debug)]
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+    /// One lock per key being fetched, so concurrent misses on the same key fetch it once
+    flights: Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration) -> TtlCache<K, V> {
+        TtlCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            flights: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key)
+            .filter(|(stored_at, _)| stored_at.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Stores `value`, dropping the entries that already expired
+    pub fn insert(&self, key: K, value: V) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (stored_at, _)| stored_at.elapsed() < self.ttl);
+        entries.insert(key, (Instant::now(), value));
+    }
+
+    /// The lock to hold while fetching `key`, dropping the locks nobody holds anymore
+    pub fn flight(&self, key: &K) -> Arc<tokio::sync::Mutex<()>> {
+        let mut flights = self.flights.lock().unwrap();
+        flights.retain(|_, flight| Arc::strong_count(flight) > 1);
+        flights.entry(key.clone()).or_default().clone()
+    }
+}
@@ -0,0 +1,42 @@
+use dotenv::dotenv;
+use std::env;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// How long search and appointment responses are reused, zero to always call the site
+    pub ttl: Duration,
+}
+
+impl CacheConfig {
+    pub fn builder() -> CacheConfigBuilder {
+        CacheConfigBuilder::new()
+    }
+}
+
+pub struct CacheConfigBuilder {
+    pub ttl: Duration,
+}
+
+impl CacheConfigBuilder {
+    pub fn new() -> CacheConfigBuilder {
+        dotenv().ok();
+        let ttl = match env::var("UPSTREAM_CACHE_TTL_SECS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid UPSTREAM_CACHE_TTL_SECS, using default");
+                60
+            }),
+            Err(_) => 60,
+        };
+
+        CacheConfigBuilder {
+            ttl: Duration::from_secs(ttl),
+        }
+    }
+
+    pub fn build(self) -> CacheConfig {
+        CacheConfig {
+            ttl: self.ttl,
+        }
+    }
+}
\ No newline at end of file
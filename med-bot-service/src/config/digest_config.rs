@@ -0,0 +1,86 @@
+use cron::Schedule;
+use dotenv::dotenv;
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Every day at 01:00 UTC, 08:00 in Vietnam
+const DEFAULT_CRON: &str = "0 0 1 * * *";
+
+#[derive(Debug, Clone)]
+pub struct DigestConfig {
+    pub schedule: Schedule,
+    /// `;` separated addresses receiving the digest
+    pub recipients: String,
+    /// How long items already sent in a digest are kept
+    pub retention: Duration,
+    /// How long claimed items wait for their digest to be marked sent before the next digest takes them back
+    pub claim_timeout: Duration,
+}
+
+impl DigestConfig {
+    pub fn builder() -> DigestConfigBuilder {
+        DigestConfigBuilder::new()
+    }
+}
+
+pub struct DigestConfigBuilder {
+    pub schedule: Schedule,
+    pub recipients: String,
+    pub retention: Duration,
+    pub claim_timeout: Duration,
+}
+
+impl DigestConfigBuilder {
+    pub fn new() -> DigestConfigBuilder {
+        dotenv().ok();
+        let schedule = match env::var("DIGEST_CRON") {
+            Ok(v) => Schedule::from_str(&v).unwrap_or_else(|e| {
+                log::error!("Invalid DIGEST_CRON {}: {}, using default", v, e);
+                Schedule::from_str(DEFAULT_CRON).unwrap()
+            }),
+            Err(_) => Schedule::from_str(DEFAULT_CRON).unwrap(),
+        };
+
+        // Falls back to the recipients of immediate alerts
+        let recipients = match env::var("DIGEST_EMAIL").or_else(|_| env::var("TARGET_EMAIL")) {
+            Ok(v) => v.to_string(),
+            Err(_) => {
+                log::error!("Error loading DIGEST_EMAIL from env");
+                "UNKNOWN".to_string()
+            }
+        };
+
+        let retention_days = match env::var("DIGEST_RETENTION_DAYS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid DIGEST_RETENTION_DAYS, using default");
+                30
+            }).max(1),
+            Err(_) => 30,
+        };
+
+        let claim_timeout_minutes = match env::var("DIGEST_CLAIM_TIMEOUT_MINUTES") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid DIGEST_CLAIM_TIMEOUT_MINUTES, using default");
+                30
+            }).max(1),
+            Err(_) => 30,
+        };
+
+        DigestConfigBuilder {
+            schedule,
+            recipients,
+            retention: Duration::from_secs(retention_days * 24 * 60 * 60),
+            claim_timeout: Duration::from_secs(claim_timeout_minutes * 60),
+        }
+    }
+
+    pub fn build(self) -> DigestConfig {
+        DigestConfig {
+            schedule: self.schedule,
+            recipients: self.recipients,
+            retention: self.retention,
+            claim_timeout: self.claim_timeout,
+        }
+    }
+}
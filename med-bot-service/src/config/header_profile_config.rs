@@ -0,0 +1,193 @@
+use dotenv::dotenv;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+
+const BUILT_IN_PROFILE: &str = "firefox";
+/// Header names hinting at credentials, masked in logs
+const SECRET_HEADER_HINTS: [&str; 6] = ["auth", "token", "cookie", "key", "secret", "session"];
+
+/// Browser identity sent with the booking site requests. Its `Debug` output masks credential-like headers.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HeaderProfile {
+    pub user_agent: String,
+    pub locale: String,
+    /// `platform` header of the doctor search
+    pub search_platform: String,
+    /// `platform` header of the appointment schedule
+    pub appointment_platform: String,
+    /// Any other header, e.g. `Accept-Language` or `Sec-Fetch-Mode`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl HeaderProfile {
+    /// The Firefox 130 headers the bot has always sent
+    pub fn firefox() -> HeaderProfile {
+        let headers = [
+            ("Accept", "application/json, text/plain, */*"),
+            ("Accept-Language", "en-US,en;q=0.5"),
+            ("Accept-Encoding", "gzip, deflate, br, zstd"),
+            ("Connection", "keep-alive"),
+            ("Sec-Fetch-Dest", "empty"),
+            ("Sec-Fetch-Mode", "cors"),
+            ("Sec-Fetch-Site", "cross-site"),
+        ];
+
+        HeaderProfile {
+            user_agent: "Mozilla/5.0 (X11; Linux x86_64; rv:130.0) Gecko/20100101 Firefox/130.0".to_string(),
+            locale: "vi".to_string(),
+            search_platform: "web".to_string(),
+            appointment_platform: "pc".to_string(),
+            headers: headers.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// The headers as printed in logs, with credential-like values hidden
+    fn masked_headers(&self) -> BTreeMap<&str, &str> {
+        self.headers.iter()
+            .map(|(name, value)| {
+                let lowercase_name = name.to_lowercase();
+                if SECRET_HEADER_HINTS.iter().any(|hint| lowercase_name.contains(hint)) {
+                    (name.as_str(), "***")
+                } else {
+                    (name.as_str(), value.as_str())
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for HeaderProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeaderProfile")
+            .field("user_agent", &self.user_agent)
+            .field("locale", &self.locale)
+            .field("search_platform", &self.search_platform)
+            .field("appointment_platform", &self.appointment_platform)
+            .field("headers", &self.masked_headers())
+            .finish()
+    }
+}
+
+/// Named header profiles, selected per target with `header_profile`
+#[derive(Debug, Clone)]
+pub struct HeaderProfiles {
+    pub default_profile: String,
+    pub profiles: BTreeMap<String, HeaderProfile>,
+}
+
+impl HeaderProfiles {
+    pub fn builder() -> HeaderProfilesBuilder {
+        HeaderProfilesBuilder::new()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.profiles.contains_key(name)
+    }
+
+    /// The profile called `name`, or the default one when no name is given
+    pub fn resolve(&self, name: Option<&str>) -> (&str, &HeaderProfile) {
+        let name = name.unwrap_or(&self.default_profile);
+        match self.profiles.get_key_value(name) {
+            Some((name, profile)) => (name, profile),
+            None => {
+                log::warn!("Unknown header profile {}, using {}", name, self.default_profile);
+                let (name, profile) = self.profiles.get_key_value(&self.default_profile)
+                    .expect("Default header profile not loaded");
+                (name, profile)
+            }
+        }
+    }
+}
+
+pub struct HeaderProfilesBuilder {
+    pub default_profile: String,
+    pub profiles: BTreeMap<String, HeaderProfile>,
+}
+
+impl HeaderProfilesBuilder {
+    pub fn new() -> HeaderProfilesBuilder {
+        dotenv().ok();
+        let mut profiles = BTreeMap::new();
+        profiles.insert(BUILT_IN_PROFILE.to_string(), HeaderProfile::firefox());
+
+        // JSON object of profiles by name, a `firefox` entry replaces the built-in one
+        if let Ok(path) = env::var("HEADER_PROFILES_FILE") {
+            if !path.is_empty() {
+                let loaded = fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|raw_json| serde_json::from_str::<BTreeMap<String, HeaderProfile>>(&raw_json)
+                        .map_err(|e| e.to_string()));
+                match loaded {
+                    Ok(loaded) => {
+                        log::info!("Loaded header profiles {:?} from {}", loaded.keys().collect::<Vec<&String>>(), path);
+                        profiles.extend(loaded);
+                    }
+                    Err(e) => log::error!("Invalid HEADER_PROFILES_FILE {}: {}, using the built-in profile", path, e),
+                }
+            }
+        }
+
+        let default_profile = match env::var("DEFAULT_HEADER_PROFILE") {
+            Ok(v) if profiles.contains_key(&v) => v,
+            Ok(v) if !v.is_empty() => {
+                log::error!("Unknown DEFAULT_HEADER_PROFILE {}, using {}", v, BUILT_IN_PROFILE);
+                BUILT_IN_PROFILE.to_string()
+            }
+            _ => BUILT_IN_PROFILE.to_string(),
+        };
+
+        HeaderProfilesBuilder {
+            default_profile,
+            profiles,
+        }
+    }
+
+    pub fn build(self) -> HeaderProfiles {
+        HeaderProfiles {
+            default_profile: self.default_profile,
+            profiles: self.profiles,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile_with_secrets() -> HeaderProfile {
+        let mut profile = HeaderProfile::firefox();
+        profile.headers.insert("Authorization".to_string(), "Bearer secret-token".to_string());
+        profile.headers.insert("Cookie".to_string(), "session=secret-cookie".to_string());
+        profile.headers.insert("X-Api-Key".to_string(), "secret-key".to_string());
+        profile
+    }
+
+    #[test]
+    fn debug_masks_secret_headers() {
+        let debug = format!("{:?}", profile_with_secrets());
+
+        assert!(!debug.contains("secret"));
+        assert!(debug.contains(r#""Authorization": "***""#));
+        assert!(debug.contains(r#""Cookie": "***""#));
+        assert!(debug.contains(r#""X-Api-Key": "***""#));
+        assert!(debug.contains(r#""Accept-Language": "en-US,en;q=0.5""#));
+        assert!(debug.contains("Firefox/130.0"));
+    }
+
+    #[test]
+    fn profiles_debug_masks_secret_headers() {
+        let mut profiles = BTreeMap::new();
+        profiles.insert("custom".to_string(), profile_with_secrets());
+        let profiles = HeaderProfiles {
+            default_profile: "custom".to_string(),
+            profiles,
+        };
+
+        assert!(!format!("{:?}", profiles).contains("secret"));
+    }
+}
@@ -5,11 +5,11 @@ use lettre::transport::smtp::authentication::Credentials;
 #[derive(Debug, Clone)]
 pub struct MailClient {
     pub smtp_host: String,
-    pub smtp_username: String,
-    pub smtp_password: String,
     pub credentials: Credentials,
     pub from_email: String,
     pub target_email: String,
+    /// Directory with mail templates overriding the built-in ones
+    pub template_dir: Option<String>,
 }
 
 impl MailClient {
@@ -20,11 +20,10 @@ impl MailClient {
 
 pub struct MailClientBuilder {
     pub smtp_host: String,
-    pub smtp_username: String,
-    pub smtp_password: String,
     pub credentials: Credentials,
     pub from_email: String,
     pub target_email: String,
+    pub template_dir: Option<String>,
 }
 
 impl MailClientBuilder {
@@ -72,17 +71,19 @@ impl MailClientBuilder {
 
         // Create SMTP client credentials using username and password
         let creds = Credentials::new(
-            smtp_username.clone().to_string(),
-            smtp_password.clone().to_string()
+            smtp_username,
+            smtp_password
         );
 
+        let template_dir = env::var("MAIL_TEMPLATE_DIR").ok()
+            .filter(|template_dir| !template_dir.is_empty());
+
         MailClientBuilder {
             smtp_host,
-            smtp_username,
-            smtp_password,
             credentials: creds,
             from_email,
             target_email,
+            template_dir,
         }
     }
 
@@ -90,11 +91,10 @@ impl MailClientBuilder {
     pub fn build(self) -> MailClient {
         MailClient {
             smtp_host: self.smtp_host,
-            smtp_username: self.smtp_username,
-            smtp_password: self.smtp_password,
             credentials: self.credentials,
             from_email: self.from_email,
             target_email: self.target_email,
+            template_dir: self.template_dir,
         }
     }
 }
\ No newline at end of file
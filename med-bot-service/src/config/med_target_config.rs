@@ -1,3 +1,4 @@
+use crate::config::header_profile_config::HeaderProfiles;
 use dotenv::dotenv;
 use std::env;
 
@@ -7,6 +8,8 @@ pub struct MedTarget {
     pub search_med_api: String,
     pub origin_header: String,
     pub appid_header: String,
+    pub analyze_concurrency: usize,
+    pub header_profiles: HeaderProfiles,
 }
 
 impl MedTarget {
@@ -20,6 +23,8 @@ pub struct MedTargetBuilder {
     pub search_med_api: String,
     pub origin_header: String,
     pub appid_header: String,
+    pub analyze_concurrency: usize,
+    pub header_profiles: HeaderProfiles,
 }
 
 impl MedTargetBuilder {
@@ -57,11 +62,24 @@ impl MedTargetBuilder {
             }
         };
 
+        let analyze_concurrency = match env::var("ANALYZE_CONCURRENCY") {
+            Ok(v) => v.parse::<usize>().unwrap_or_else(|_| {
+                log::error!("Invalid ANALYZE_CONCURRENCY, using default");
+                3
+            }).max(1),
+            Err(_) => 3,
+        };
+
+        let header_profiles = HeaderProfiles::builder()
+            .build();
+
         MedTargetBuilder {
             appointment_api,
             search_med_api,
             origin_header,
-            appid_header: appid_header,
+            appid_header,
+            analyze_concurrency,
+            header_profiles,
         }
     }
 
@@ -72,6 +90,8 @@ impl MedTargetBuilder {
             search_med_api: self.search_med_api,
             origin_header: self.origin_header,
             appid_header: self.appid_header,
+            analyze_concurrency: self.analyze_concurrency,
+            header_profiles: self.header_profiles,
         }
     }
 }
\ No newline at end of file
@@ -1,3 +1,15 @@
 pub mod mongo_config;
 pub mod mail_config;
-pub mod med_target_config;
\ No newline at end of file
+pub mod med_target_config;
+pub mod notifier_config;
+pub mod telegram_config;
+pub mod webhook_config;
+pub mod scheduler_config;
+pub mod observation_config;
+pub mod digest_config;
+pub mod provider_config;
+pub mod retry_config;
+pub mod rate_limit_config;
+pub mod header_profile_config;
+pub mod cache_config;
+pub mod run_config;
\ No newline at end of file
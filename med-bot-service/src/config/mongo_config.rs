@@ -1,6 +1,5 @@
-use crate::models::documents::{Doctor, User};
+use crate::models::documents::{DigestItem, Doctor, NotificationLedgerEntry, RunLock, RunRecord, SlotObservation, SlotSnapshot};
 use dotenv::dotenv;
-use mongodb::bson::Document;
 use mongodb::{
     Client, Collection,
 };
@@ -8,9 +7,13 @@ use std::env;
 
 #[derive(Debug, Clone)]
 pub struct MongoClient {
-    pub dynamic_collection: Collection<Document>,
-    pub user_collection: Collection<User>,
     pub doctor_collection: Collection<Doctor>,
+    pub slot_snapshot_collection: Collection<SlotSnapshot>,
+    pub run_lock_collection: Collection<RunLock>,
+    pub run_record_collection: Collection<RunRecord>,
+    pub slot_observation_collection: Collection<SlotObservation>,
+    pub notification_ledger_collection: Collection<NotificationLedgerEntry>,
+    pub digest_item_collection: Collection<DigestItem>,
 }
 
 impl MongoClient {
@@ -20,9 +23,13 @@ impl MongoClient {
 }
 
 pub struct MongoClientBuilder {
-    pub dynamic_collection: Option<Collection<Document>>,
-    pub user_collection: Option<Collection<User>>,
     pub doctor_collection: Option<Collection<Doctor>>,
+    pub slot_snapshot_collection: Option<Collection<SlotSnapshot>>,
+    pub run_lock_collection: Option<Collection<RunLock>>,
+    pub run_record_collection: Option<Collection<RunRecord>>,
+    pub slot_observation_collection: Option<Collection<SlotObservation>>,
+    pub notification_ledger_collection: Option<Collection<NotificationLedgerEntry>>,
+    pub digest_item_collection: Option<Collection<DigestItem>>,
     client: Client,
 }
 
@@ -46,38 +53,74 @@ impl MongoClientBuilder {
 
         MongoClientBuilder {
             client,
-            dynamic_collection: None,
-            user_collection: None,
             doctor_collection: None,
+            slot_snapshot_collection: None,
+            run_lock_collection: None,
+            run_record_collection: None,
+            slot_observation_collection: None,
+            notification_ledger_collection: None,
+            digest_item_collection: None,
         }
     }
 
-    pub fn with_dynamic_collection(mut self) -> MongoClientBuilder {
+    pub fn with_doctor_collection(mut self) -> MongoClientBuilder {
         let db = self.client.database("med_tool");
-        let col: Collection<Document> = db.collection("Dynamic");
-        self.dynamic_collection = Some(col);
+        let col: Collection<Doctor> = db.collection("doctor");
+        self.doctor_collection = Some(col);
         self
     }
 
-    pub fn with_user_collection(mut self) -> MongoClientBuilder {
+    pub fn with_slot_snapshot_collection(mut self) -> MongoClientBuilder {
         let db = self.client.database("med_tool");
-        let col: Collection<User> = db.collection("user");
-        self.user_collection = Some(col);
+        let col: Collection<SlotSnapshot> = db.collection("slot_snapshot");
+        self.slot_snapshot_collection = Some(col);
         self
     }
 
-    pub fn with_doctor_collection(mut self) -> MongoClientBuilder {
+    pub fn with_run_lock_collection(mut self) -> MongoClientBuilder {
         let db = self.client.database("med_tool");
-        let col: Collection<Doctor> = db.collection("doctor");
-        self.doctor_collection = Some(col);
+        let col: Collection<RunLock> = db.collection("run_lock");
+        self.run_lock_collection = Some(col);
+        self
+    }
+
+    pub fn with_run_record_collection(mut self) -> MongoClientBuilder {
+        let db = self.client.database("med_tool");
+        let col: Collection<RunRecord> = db.collection("run_record");
+        self.run_record_collection = Some(col);
+        self
+    }
+
+    pub fn with_slot_observation_collection(mut self) -> MongoClientBuilder {
+        let db = self.client.database("med_tool");
+        let col: Collection<SlotObservation> = db.collection("slot_observation");
+        self.slot_observation_collection = Some(col);
+        self
+    }
+
+    pub fn with_notification_ledger_collection(mut self) -> MongoClientBuilder {
+        let db = self.client.database("med_tool");
+        let col: Collection<NotificationLedgerEntry> = db.collection("notification_ledger");
+        self.notification_ledger_collection = Some(col);
+        self
+    }
+
+    pub fn with_digest_item_collection(mut self) -> MongoClientBuilder {
+        let db = self.client.database("med_tool");
+        let col: Collection<DigestItem> = db.collection("digest_item");
+        self.digest_item_collection = Some(col);
         self
     }
 
     pub fn build(self) -> MongoClient {
         MongoClient {
-            dynamic_collection: self.dynamic_collection.expect("Dynamic collection not initialized"),
-            user_collection: self.user_collection.expect("User collection not initialized"),
             doctor_collection: self.doctor_collection.expect("Doctor collection not initialized"),
+            slot_snapshot_collection: self.slot_snapshot_collection.expect("Slot snapshot collection not initialized"),
+            run_lock_collection: self.run_lock_collection.expect("Run lock collection not initialized"),
+            run_record_collection: self.run_record_collection.expect("Run record collection not initialized"),
+            slot_observation_collection: self.slot_observation_collection.expect("Slot observation collection not initialized"),
+            notification_ledger_collection: self.notification_ledger_collection.expect("Notification ledger collection not initialized"),
+            digest_item_collection: self.digest_item_collection.expect("Digest item collection not initialized"),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use dotenv::dotenv;
+use std::env;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct NotifierConfig {
+    pub channels: Vec<String>,
+    pub cooldown: Duration,
+    /// How long notification ledger entries are kept, never shorter than the cooldown
+    pub ledger_retention: Duration,
+}
+
+impl NotifierConfig {
+    pub fn builder() -> NotifierConfigBuilder {
+        NotifierConfigBuilder::new()
+    }
+
+    pub fn is_enabled(&self, channel: &str) -> bool {
+        self.channels.iter().any(|enabled| enabled == channel)
+    }
+}
+
+pub struct NotifierConfigBuilder {
+    pub channels: Vec<String>,
+    pub cooldown: Duration,
+    /// How long notification ledger entries are kept, never shorter than the cooldown
+    pub ledger_retention: Duration,
+}
+
+impl NotifierConfigBuilder {
+    pub fn new() -> NotifierConfigBuilder {
+        dotenv().ok();
+        // Comma separated list of channels, e.g. `mail,telegram,webhook`
+        let channels = match env::var("NOTIFIERS") {
+            Ok(v) => v.split(',')
+                .map(|channel| channel.trim().to_lowercase())
+                .filter(|channel| !channel.is_empty())
+                .collect(),
+            Err(_) => vec!["mail".to_string()],
+        };
+
+        // Minimum time between two notifications about the same slot on the same channel
+        let cooldown_minutes = match env::var("NOTIFICATION_COOLDOWN_MINUTES") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid NOTIFICATION_COOLDOWN_MINUTES, using default");
+                60
+            }),
+            Err(_) => 60,
+        };
+
+        let ledger_retention_days = match env::var("NOTIFICATION_LEDGER_RETENTION_DAYS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid NOTIFICATION_LEDGER_RETENTION_DAYS, using default");
+                30
+            }).max(1),
+            Err(_) => 30,
+        };
+        let cooldown = Duration::from_secs(cooldown_minutes * 60);
+
+        NotifierConfigBuilder {
+            channels,
+            cooldown,
+            ledger_retention: Duration::from_secs(ledger_retention_days * 24 * 60 * 60).max(cooldown),
+        }
+    }
+
+
+    pub fn build(self) -> NotifierConfig {
+        NotifierConfig {
+            channels: self.channels,
+            cooldown: self.cooldown,
+            ledger_retention: self.ledger_retention,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use dotenv::dotenv;
+use std::env;
+use std::time::Duration;
+
+const DEFAULT_RETENTION_DAYS: u64 = 90;
+
+#[derive(Debug, Clone)]
+pub struct ObservationConfig {
+    /// How long slot observations are kept before Mongo's TTL monitor removes them
+    pub retention: Duration,
+}
+
+impl ObservationConfig {
+    pub fn builder() -> ObservationConfigBuilder {
+        ObservationConfigBuilder::new()
+    }
+}
+
+pub struct ObservationConfigBuilder {
+    pub retention: Duration,
+}
+
+impl ObservationConfigBuilder {
+    pub fn new() -> ObservationConfigBuilder {
+        dotenv().ok();
+        let retention_days = match env::var("SLOT_OBSERVATION_RETENTION_DAYS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid SLOT_OBSERVATION_RETENTION_DAYS, using default");
+                DEFAULT_RETENTION_DAYS
+            }).max(1),
+            Err(_) => DEFAULT_RETENTION_DAYS,
+        };
+
+        ObservationConfigBuilder {
+            retention: Duration::from_secs(retention_days * 24 * 60 * 60),
+        }
+    }
+
+    pub fn build(self) -> ObservationConfig {
+        ObservationConfig {
+            retention: self.retention,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use dotenv::dotenv;
+use std::env;
+
+const DEFAULT_FIXTURE_DIR: &str = "./fixtures";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    /// The live booking site, at `SEARCH_MED_API` and `APPOINTMENT_API`
+    Http,
+    /// Recorded responses loaded from `MOCK_FIXTURE_DIR`, for offline runs
+    Mock,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProviderConfig {
+    pub kind: ProviderKind,
+    pub fixture_dir: String,
+}
+
+impl ProviderConfig {
+    pub fn builder() -> ProviderConfigBuilder {
+        ProviderConfigBuilder::new()
+    }
+}
+
+pub struct ProviderConfigBuilder {
+    pub kind: ProviderKind,
+    pub fixture_dir: String,
+}
+
+impl ProviderConfigBuilder {
+    pub fn new() -> ProviderConfigBuilder {
+        dotenv().ok();
+        let kind = match env::var("BOOKING_PROVIDER").as_deref() {
+            Ok("mock") => ProviderKind::Mock,
+            Ok("http") | Err(_) => ProviderKind::Http,
+            Ok(v) => {
+                log::error!("Invalid BOOKING_PROVIDER {}, using http", v);
+                ProviderKind::Http
+            }
+        };
+
+        let fixture_dir = match env::var("MOCK_FIXTURE_DIR") {
+            Ok(v) if !v.is_empty() => v.to_string(),
+            _ => DEFAULT_FIXTURE_DIR.to_string(),
+        };
+
+        ProviderConfigBuilder {
+            kind,
+            fixture_dir,
+        }
+    }
+
+    pub fn build(self) -> ProviderConfig {
+        ProviderConfig {
+            kind: self.kind,
+            fixture_dir: self.fixture_dir,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,54 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Requests to the booking site allowed per minute, over all targets
+    pub requests_per_minute: u32,
+    /// Booking site requests in flight at once, also the largest burst the bucket allows
+    pub max_concurrency: usize,
+}
+
+impl RateLimitConfig {
+    pub fn builder() -> RateLimitConfigBuilder {
+        RateLimitConfigBuilder::new()
+    }
+}
+
+pub struct RateLimitConfigBuilder {
+    pub requests_per_minute: u32,
+    pub max_concurrency: usize,
+}
+
+impl RateLimitConfigBuilder {
+    pub fn new() -> RateLimitConfigBuilder {
+        dotenv().ok();
+        let requests_per_minute = match env::var("UPSTREAM_REQUESTS_PER_MINUTE") {
+            Ok(v) => v.parse::<u32>().unwrap_or_else(|_| {
+                log::error!("Invalid UPSTREAM_REQUESTS_PER_MINUTE, using default");
+                30
+            }).max(1),
+            Err(_) => 30,
+        };
+
+        let max_concurrency = match env::var("UPSTREAM_MAX_CONCURRENCY") {
+            Ok(v) => v.parse::<usize>().unwrap_or_else(|_| {
+                log::error!("Invalid UPSTREAM_MAX_CONCURRENCY, using default");
+                2
+            }).max(1),
+            Err(_) => 2,
+        };
+
+        RateLimitConfigBuilder {
+            requests_per_minute,
+            max_concurrency,
+        }
+    }
+
+    pub fn build(self) -> RateLimitConfig {
+        RateLimitConfig {
+            requests_per_minute: self.requests_per_minute,
+            max_concurrency: self.max_concurrency,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,93 @@
+use dotenv::dotenv;
+use std::env;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Attempts per upstream call, the first one included
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on each following one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Consecutive failed upstream requests that open the circuit
+    pub breaker_threshold: u32,
+    /// How long an open circuit rejects calls before letting a trial request through
+    pub breaker_cooldown: Duration,
+}
+
+impl RetryConfig {
+    pub fn builder() -> RetryConfigBuilder {
+        RetryConfigBuilder::new()
+    }
+}
+
+pub struct RetryConfigBuilder {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+}
+
+impl RetryConfigBuilder {
+    pub fn new() -> RetryConfigBuilder {
+        dotenv().ok();
+        let max_attempts = match env::var("UPSTREAM_RETRY_MAX_ATTEMPTS") {
+            Ok(v) => v.parse::<u32>().unwrap_or_else(|_| {
+                log::error!("Invalid UPSTREAM_RETRY_MAX_ATTEMPTS, using default");
+                3
+            }).max(1),
+            Err(_) => 3,
+        };
+
+        let base_delay = match env::var("UPSTREAM_RETRY_BASE_DELAY_MS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid UPSTREAM_RETRY_BASE_DELAY_MS, using default");
+                500
+            }),
+            Err(_) => 500,
+        };
+
+        let max_delay = match env::var("UPSTREAM_RETRY_MAX_DELAY_MS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid UPSTREAM_RETRY_MAX_DELAY_MS, using default");
+                10_000
+            }),
+            Err(_) => 10_000,
+        }.max(base_delay);
+
+        let breaker_threshold = match env::var("UPSTREAM_BREAKER_THRESHOLD") {
+            Ok(v) => v.parse::<u32>().unwrap_or_else(|_| {
+                log::error!("Invalid UPSTREAM_BREAKER_THRESHOLD, using default");
+                5
+            }).max(1),
+            Err(_) => 5,
+        };
+
+        let breaker_cooldown = match env::var("UPSTREAM_BREAKER_COOLDOWN_SECS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid UPSTREAM_BREAKER_COOLDOWN_SECS, using default");
+                60
+            }),
+            Err(_) => 60,
+        };
+
+        RetryConfigBuilder {
+            max_attempts,
+            base_delay: Duration::from_millis(base_delay),
+            max_delay: Duration::from_millis(max_delay),
+            breaker_threshold,
+            breaker_cooldown: Duration::from_secs(breaker_cooldown),
+        }
+    }
+
+    pub fn build(self) -> RetryConfig {
+        RetryConfig {
+            max_attempts: self.max_attempts,
+            base_delay: self.base_delay,
+            max_delay: self.max_delay,
+            breaker_threshold: self.breaker_threshold,
+            breaker_cooldown: self.breaker_cooldown,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,44 @@
+use dotenv::dotenv;
+use std::env;
+use std::time::Duration;
+
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    /// How long run records are kept before Mongo's TTL monitor removes them
+    pub retention: Duration,
+}
+
+impl RunConfig {
+    pub fn builder() -> RunConfigBuilder {
+        RunConfigBuilder::new()
+    }
+}
+
+pub struct RunConfigBuilder {
+    pub retention: Duration,
+}
+
+impl RunConfigBuilder {
+    pub fn new() -> RunConfigBuilder {
+        dotenv().ok();
+        let retention_days = match env::var("RUN_RECORD_RETENTION_DAYS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid RUN_RECORD_RETENTION_DAYS, using default");
+                DEFAULT_RETENTION_DAYS
+            }).max(1),
+            Err(_) => DEFAULT_RETENTION_DAYS,
+        };
+
+        RunConfigBuilder {
+            retention: Duration::from_secs(retention_days * 24 * 60 * 60),
+        }
+    }
+
+    pub fn build(self) -> RunConfig {
+        RunConfig {
+            retention: self.retention,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use cron::Schedule;
+use dotenv::dotenv;
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Every 8 hours
+const DEFAULT_CRON: &str = "0 0 0/8 * * *";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulerMode {
+    /// Every target polls on its cron schedule
+    Cron,
+    /// Targets without their own `poll_cron` poll on an interval adapted to the window and slot churn
+    Adaptive,
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub default_schedule: Schedule,
+    pub failure_threshold: u32,
+    pub jitter: Duration,
+    pub mode: SchedulerMode,
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+    pub requests_per_hour: u32,
+    pub lock_ttl: Duration,
+}
+
+impl SchedulerConfig {
+    pub fn builder() -> SchedulerConfigBuilder {
+        SchedulerConfigBuilder::new()
+    }
+}
+
+pub struct SchedulerConfigBuilder {
+    pub default_schedule: Schedule,
+    pub failure_threshold: u32,
+    pub jitter: Duration,
+    pub mode: SchedulerMode,
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+    pub requests_per_hour: u32,
+    pub lock_ttl: Duration,
+}
+
+impl SchedulerConfigBuilder {
+    pub fn new() -> SchedulerConfigBuilder {
+        dotenv().ok();
+        //0 0/5 * * * * template for every 5 minutes
+        let default_schedule = match env::var("SCHEDULER_CRON") {
+            Ok(v) => Schedule::from_str(&v).unwrap_or_else(|e| {
+                log::error!("Invalid SCHEDULER_CRON {}: {}, using default", v, e);
+                Schedule::from_str(DEFAULT_CRON).unwrap()
+            }),
+            Err(_) => Schedule::from_str(DEFAULT_CRON).unwrap(),
+        };
+
+        // Consecutive failed runs before the health check reports degraded
+        let failure_threshold = match env::var("SCHEDULER_FAILURE_THRESHOLD") {
+            Ok(v) => v.parse::<u32>().unwrap_or_else(|_| {
+                log::error!("Invalid SCHEDULER_FAILURE_THRESHOLD, using default");
+                3
+            }).max(1),
+            Err(_) => 3,
+        };
+
+        // Upper bound of the random delay added to each tick, so polls don't all land on :00
+        let jitter = match env::var("SCHEDULER_JITTER_SECS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid SCHEDULER_JITTER_SECS, using default");
+                0
+            }),
+            Err(_) => 0,
+        };
+
+        let mode = match env::var("SCHEDULER_MODE").as_deref() {
+            Ok("adaptive") => SchedulerMode::Adaptive,
+            Ok("cron") | Err(_) => SchedulerMode::Cron,
+            Ok(v) => {
+                log::error!("Invalid SCHEDULER_MODE {}, using cron", v);
+                SchedulerMode::Cron
+            }
+        };
+
+        // Bounds of the adaptive polling interval
+        let min_interval = match env::var("SCHEDULER_MIN_INTERVAL_SECS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid SCHEDULER_MIN_INTERVAL_SECS, using default");
+                300
+            }).max(1),
+            Err(_) => 300,
+        };
+        let max_interval = match env::var("SCHEDULER_MAX_INTERVAL_SECS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid SCHEDULER_MAX_INTERVAL_SECS, using default");
+                21600
+            }).max(min_interval),
+            Err(_) => 21600.max(min_interval),
+        };
+
+        // Upstream requests all adaptive targets together may make per hour
+        let requests_per_hour = match env::var("SCHEDULER_REQUESTS_PER_HOUR") {
+            Ok(v) => v.parse::<u32>().unwrap_or_else(|_| {
+                log::error!("Invalid SCHEDULER_REQUESTS_PER_HOUR, using default");
+                120
+            }).max(1),
+            Err(_) => 120,
+        };
+
+        // How long a run lock is held before another replica may take it over,
+        // should exceed the longest analysis
+        let lock_ttl = match env::var("SCHEDULER_LOCK_TTL_SECS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                log::error!("Invalid SCHEDULER_LOCK_TTL_SECS, using default");
+                900
+            }).max(1),
+            Err(_) => 900,
+        };
+
+        SchedulerConfigBuilder {
+            default_schedule,
+            failure_threshold,
+            jitter: Duration::from_secs(jitter),
+            mode,
+            min_interval: Duration::from_secs(min_interval),
+            max_interval: Duration::from_secs(max_interval),
+            requests_per_hour,
+            lock_ttl: Duration::from_secs(lock_ttl),
+        }
+    }
+
+
+    pub fn build(self) -> SchedulerConfig {
+        SchedulerConfig {
+            default_schedule: self.default_schedule,
+            failure_threshold: self.failure_threshold,
+            jitter: self.jitter,
+            mode: self.mode,
+            min_interval: self.min_interval,
+            max_interval: self.max_interval,
+            requests_per_hour: self.requests_per_hour,
+            lock_ttl: self.lock_ttl,
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Debug, Clone)]
+pub struct TelegramClient {
+    pub api_url: String,
+    pub bot_token: String,
+    pub chat_ids: Vec<String>,
+}
+
+impl TelegramClient {
+    pub fn builder() -> TelegramClientBuilder {
+        TelegramClientBuilder::new()
+    }
+}
+
+pub struct TelegramClientBuilder {
+    pub api_url: String,
+    pub bot_token: String,
+    pub chat_ids: Vec<String>,
+}
+
+impl TelegramClientBuilder {
+    pub fn new() -> TelegramClientBuilder {
+        dotenv().ok();
+        // Overridable so the notifier can be pointed at a local stand-in of the Bot API
+        let api_url = match env::var("TELEGRAM_API_URL") {
+            Ok(v) => v.trim_end_matches('/').to_string(),
+            Err(_) => "https://api.telegram.org".to_string(),
+        };
+
+        let bot_token = match env::var("TELEGRAM_BOT_TOKEN") {
+            Ok(v) => v.to_string(),
+            Err(_) => {
+                log::error!("Error loading TELEGRAM_BOT_TOKEN from env");
+                "UNKNOWN".to_string()
+            }
+        };
+
+        let chat_ids = match env::var("TELEGRAM_CHAT_IDS") {
+            Ok(v) => v.split(';')
+                .map(|chat_id| chat_id.trim().to_string())
+                .filter(|chat_id| !chat_id.is_empty())
+                .collect(),
+            Err(_) => {
+                log::error!("Error loading TELEGRAM_CHAT_IDS from env");
+                vec![]
+            }
+        };
+
+        TelegramClientBuilder {
+            api_url,
+            bot_token,
+            chat_ids,
+        }
+    }
+
+
+    pub fn build(self) -> TelegramClient {
+        TelegramClient {
+            api_url: self.api_url,
+            bot_token: self.bot_token,
+            chat_ids: self.chat_ids,
+        }
+    }
+}
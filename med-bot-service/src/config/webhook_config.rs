@@ -0,0 +1,48 @@
+use dotenv::dotenv;
+use std::env;
+
+#[derive(Debug, Clone)]
+pub struct WebhookClient {
+    pub url: String,
+    pub auth_token: Option<String>,
+}
+
+impl WebhookClient {
+    pub fn builder() -> WebhookClientBuilder {
+        WebhookClientBuilder::new()
+    }
+}
+
+pub struct WebhookClientBuilder {
+    pub url: String,
+    pub auth_token: Option<String>,
+}
+
+impl WebhookClientBuilder {
+    pub fn new() -> WebhookClientBuilder {
+        dotenv().ok();
+        let url = match env::var("WEBHOOK_URL") {
+            Ok(v) => v.to_string(),
+            Err(_) => {
+                log::error!("Error loading WEBHOOK_URL from env");
+                "UNKNOWN".to_string()
+            }
+        };
+
+        let auth_token = env::var("WEBHOOK_AUTH_TOKEN").ok()
+            .filter(|token| !token.is_empty());
+
+        WebhookClientBuilder {
+            url,
+            auth_token,
+        }
+    }
+
+
+    pub fn build(self) -> WebhookClient {
+        WebhookClient {
+            url: self.url,
+            auth_token: self.auth_token,
+        }
+    }
+}
@@ -1,6 +1,8 @@
+use crate::error::AppError;
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppointmentApiResponse {
     pub id: Option<String>,
 
@@ -19,7 +21,7 @@ pub struct AppointmentApiResponse {
     pub waiting_list: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Day {
     pub shifts: Vec<Shift>,
 
@@ -31,7 +33,15 @@ pub struct Day {
     pub timemiliseconds: Option<i64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Day {
+    /// The day's date, from `timemiliseconds` when upstream leaves `date` unset
+    pub fn day_date(&self) -> Option<NaiveDate> {
+        NaiveDateTime::from_timestamp_millis(self.date.or(self.timemiliseconds)?)
+            .map(|date_time| date_time.date())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Shift {
     pub id: String,
 
@@ -72,7 +82,7 @@ pub struct Shift {
     pub time_slot_in_day: Option<Vec<TimeSlot>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Service {
     pub id: String,
 
@@ -136,7 +146,7 @@ pub struct TimeSlot {
     pub priority_room: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Detail {
     pub id: String,
 
@@ -174,4 +184,138 @@ pub struct Detail {
 
     #[serde(rename = "requiredCheckInsurance")]
     pub required_check_insurance: Option<bool>,
-}
\ No newline at end of file
+}
+
+/// Query or body of `/med/appointments`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiAppointmentRequest {
+    #[serde(alias = "subjectId")]
+    pub subject_id: String,
+
+    #[serde(alias = "doctorId")]
+    pub doctor_id: String,
+
+    #[serde(alias = "serviceId")]
+    pub service_id: String,
+
+    #[serde(alias = "partnerId")]
+    pub partner_id: String,
+
+    #[serde(alias = "treeId")]
+    pub tree_id: Option<String>,
+
+    /// Ask the site even when a cached response is available
+    #[serde(default, alias = "noCache")]
+    pub no_cache: bool,
+}
+
+/// Query of the manual analysis endpoints
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AnalyzeQuery {
+    /// Ask the site for every target instead of reusing cached responses
+    #[serde(default)]
+    pub no_cache: bool,
+}
+
+impl ApiAppointmentRequest {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let required = [
+            ("subject_id", &self.subject_id),
+            ("doctor_id", &self.doctor_id),
+            ("service_id", &self.service_id),
+            ("partner_id", &self.partner_id),
+        ];
+
+        let missing = required.iter()
+            .filter(|(_, value)| value.trim().is_empty())
+            .map(|(field, _)| *field)
+            .collect::<Vec<&str>>();
+        if !missing.is_empty() {
+            return Err(AppError::BadRequest(format!("Missing required parameters: {}", missing.join(", "))));
+        }
+
+        if self.tree_id.as_ref().is_some_and(|tree_id| tree_id.trim().is_empty()) {
+            return Err(AppError::BadRequest("tree_id must not be empty".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Upstream appointment schedule normalised with ISO dates
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppointmentResponse {
+    pub id: Option<String>,
+    pub service_id: String,
+    pub service_name: Option<String>,
+    pub end: bool,
+    pub waiting_list: Option<bool>,
+    pub days: Vec<AppointmentDayResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppointmentDayResponse {
+    pub date: Option<String>,
+    pub shifts: Vec<AppointmentShiftResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppointmentShiftResponse {
+    pub id: String,
+    pub shift_code: Option<String>,
+    pub shift_name: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub room_id: Option<String>,
+    pub max_slot: Option<u32>,
+    pub doctor_change_info: Option<DoctorChangeInfo>,
+    pub time_slots: Vec<AppointmentSlotResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppointmentSlotResponse {
+    pub time_id: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub available_slot: Option<u32>,
+    pub max_slot: Option<u32>,
+}
+
+/// The day's date as `%Y-%m-%d`
+pub fn iso_date(day: &Day) -> Option<String> {
+    day.day_date().map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+impl From<AppointmentApiResponse> for AppointmentResponse {
+    fn from(response: AppointmentApiResponse) -> Self {
+        AppointmentResponse {
+            id: response.id,
+            service_id: response.detail.id,
+            service_name: response.detail.name,
+            end: response.end,
+            waiting_list: response.waiting_list,
+            days: response.days.into_iter().map(|day| AppointmentDayResponse {
+                date: iso_date(&day),
+                shifts: day.shifts.into_iter().map(|shift| AppointmentShiftResponse {
+                    id: shift.id,
+                    shift_code: shift.shift_code,
+                    shift_name: shift.shift_name,
+                    start_time: shift.start_time,
+                    end_time: shift.end_time,
+                    room_id: shift.room_id,
+                    max_slot: shift.max_slot,
+                    doctor_change_info: shift.doctor_change_info,
+                    time_slots: shift.time_slot_in_day.unwrap_or_default().into_iter()
+                        .map(|slot| AppointmentSlotResponse {
+                            time_id: slot.time_id,
+                            start_time: slot.start_time,
+                            end_time: slot.end_time,
+                            available_slot: slot.available_slot,
+                            max_slot: slot.max_slot,
+                        })
+                        .collect(),
+                }).collect(),
+            }).collect(),
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use crate::error::AppError;
+use crate::models::documents::Doctor;
+use mongodb::bson::{Bson, Document};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Body of `POST /med/doctors` and `PUT /med/doctors/{id}`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DoctorRequest {
+    pub doctor_ref_id: String,
+    pub doctor_name: String,
+    pub subject_ref_id: String,
+    #[serde(deserialize_with = "match_name")]
+    pub subject_name: String,
+    #[serde(deserialize_with = "match_name")]
+    pub service_name: String,
+    pub hospital_id: String,
+    pub city_id: String,
+    pub target_date: Option<String>,
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub window_days: Option<u32>,
+    pub weekdays: Option<Vec<String>>,
+    pub earliest_time: Option<String>,
+    pub latest_time: Option<String>,
+    pub min_available_slot: Option<u32>,
+    pub poll_cron: Option<String>,
+    pub header_profile: Option<String>,
+    #[serde(default)]
+    pub current_target: bool,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl DoctorRequest {
+    pub fn into_doctor(self) -> Doctor {
+        Doctor {
+            id: None,
+            doctor_ref_id: self.doctor_ref_id,
+            doctor_name: self.doctor_name,
+            subject_ref_id: self.subject_ref_id,
+            subject_name: self.subject_name,
+            service_name: self.service_name,
+            hospital_id: self.hospital_id,
+            city_id: self.city_id,
+            target_date: self.target_date,
+            date_from: self.date_from,
+            date_to: self.date_to,
+            window_days: self.window_days,
+            weekdays: self.weekdays,
+            earliest_time: self.earliest_time,
+            latest_time: self.latest_time,
+            min_available_slot: self.min_available_slot,
+            poll_cron: self.poll_cron,
+            header_profile: self.header_profile,
+            current_target: self.current_target,
+            active: self.active,
+        }
+    }
+}
+
+/// Body of `PATCH /med/doctors/{id}`, only the given fields are changed. Optional fields set
+/// to `null` are cleared.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DoctorPatchRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doctor_ref_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doctor_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_ref_id: Option<String>,
+    #[serde(default, deserialize_with = "optional_match_name", skip_serializing_if = "Option::is_none")]
+    pub subject_name: Option<String>,
+    #[serde(default, deserialize_with = "optional_match_name", skip_serializing_if = "Option::is_none")]
+    pub service_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hospital_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city_id: Option<String>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub target_date: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub date_from: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub date_to: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub window_days: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub weekdays: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub earliest_time: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub latest_time: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub min_available_slot: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub poll_cron: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub header_profile: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_target: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+}
+
+impl DoctorPatchRequest {
+    pub fn apply(self, doctor: &mut Doctor) {
+        if let Some(v) = self.doctor_ref_id { doctor.doctor_ref_id = v; }
+        if let Some(v) = self.doctor_name { doctor.doctor_name = v; }
+        if let Some(v) = self.subject_ref_id { doctor.subject_ref_id = v; }
+        if let Some(v) = self.subject_name { doctor.subject_name = v; }
+        if let Some(v) = self.service_name { doctor.service_name = v; }
+        if let Some(v) = self.hospital_id { doctor.hospital_id = v; }
+        if let Some(v) = self.city_id { doctor.city_id = v; }
+        if let Some(v) = self.target_date { doctor.target_date = v; }
+        if let Some(v) = self.date_from { doctor.date_from = v; }
+        if let Some(v) = self.date_to { doctor.date_to = v; }
+        if let Some(v) = self.window_days { doctor.window_days = v; }
+        if let Some(v) = self.weekdays { doctor.weekdays = v; }
+        if let Some(v) = self.earliest_time { doctor.earliest_time = v; }
+        if let Some(v) = self.latest_time { doctor.latest_time = v; }
+        if let Some(v) = self.min_available_slot { doctor.min_available_slot = v; }
+        if let Some(v) = self.poll_cron { doctor.poll_cron = v; }
+        if let Some(v) = self.header_profile { doctor.header_profile = v; }
+        if let Some(v) = self.current_target { doctor.current_target = v; }
+        if let Some(v) = self.active { doctor.active = v; }
+    }
+
+    /// The patch as one `$set`/`$unset` update, so concurrent patches of other fields are kept
+    pub fn to_update(&self) -> Result<Document, AppError> {
+        let fields = mongodb::bson::to_document(self)
+            .map_err(|e| AppError::Internal(format!("Failed to encode doctor patch: {}", e)))?;
+
+        let mut set = Document::new();
+        let mut unset = Document::new();
+        for (field, value) in fields {
+            match value {
+                Bson::Null => { unset.insert(field, ""); }
+                value => { set.insert(field, value); }
+            }
+        }
+
+        let mut update = Document::new();
+        if !set.is_empty() {
+            update.insert("$set", set);
+        }
+        if !unset.is_empty() {
+            update.insert("$unset", unset);
+        }
+        Ok(update)
+    }
+}
+
+/// Subject and service names are compared with the lowercased upstream names, so they are
+/// stored trimmed and lowercased
+fn match_name<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer).map(|name| name.trim().to_lowercase())
+}
+
+fn optional_match_name<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer).map(|name| name.map(|name| name.trim().to_lowercase()))
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from a missing field (`None`)
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Query of `GET /med/doctors`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DoctorQuery {
+    pub city_id: Option<String>,
+    pub hospital_id: Option<String>,
+    pub active: Option<bool>,
+    pub current_target: Option<bool>,
+}
+
+/// Doctor document with its id rendered as a plain hex string
+#[derive(Debug, Serialize)]
+pub struct DoctorResponse {
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub doctor: Doctor,
+}
+
+impl From<Doctor> for DoctorResponse {
+    fn from(mut doctor: Doctor) -> Self {
+        let id = doctor.id.take().map(|id| id.to_hex());
+        DoctorResponse { id, doctor }
+    }
+}
+
+/// Body of `POST /med/doctors/resolve`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolveDoctorRequest {
+    pub doctor_name: String,
+    pub city_id: String,
+    pub subject_id: String,
+    /// Narrows candidates to subjects whose name contains this value
+    pub subject_name: Option<String>,
+    /// Narrows candidates to the service with exactly this name
+    pub service_name: Option<String>,
+    pub hospital_id: Option<String>,
+    /// Date window, preferences and polling settings copied onto every candidate
+    pub target: Option<ResolveTargetSettings>,
+    /// Persist the candidate at `pick` (default the first one)
+    #[serde(default)]
+    pub save: bool,
+    pub pick: Option<usize>,
+}
+
+/// Settings of a resolved target that are not taken from the search hit. Unknown fields,
+/// including the resolved ids, are rejected.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResolveTargetSettings {
+    pub target_date: Option<String>,
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub window_days: Option<u32>,
+    pub weekdays: Option<Vec<String>>,
+    pub earliest_time: Option<String>,
+    pub latest_time: Option<String>,
+    pub min_available_slot: Option<u32>,
+    pub poll_cron: Option<String>,
+    pub header_profile: Option<String>,
+    pub current_target: Option<bool>,
+    pub active: Option<bool>,
+}
+
+impl ResolveTargetSettings {
+    pub fn apply(&self, doctor: &mut Doctor) {
+        doctor.target_date = self.target_date.clone();
+        doctor.date_from = self.date_from.clone();
+        doctor.date_to = self.date_to.clone();
+        doctor.window_days = self.window_days;
+        doctor.weekdays = self.weekdays.clone();
+        doctor.earliest_time = self.earliest_time.clone();
+        doctor.latest_time = self.latest_time.clone();
+        doctor.min_available_slot = self.min_available_slot;
+        doctor.poll_cron = self.poll_cron.clone();
+        doctor.header_profile = self.header_profile.clone();
+        if let Some(v) = self.current_target { doctor.current_target = v; }
+        if let Some(v) = self.active { doctor.active = v; }
+    }
+}
+
+impl ResolveDoctorRequest {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let required = [
+            ("doctor_name", &self.doctor_name),
+            ("city_id", &self.city_id),
+            ("subject_id", &self.subject_id),
+        ];
+
+        let missing = required.iter()
+            .filter(|(_, value)| value.trim().is_empty())
+            .map(|(field, _)| *field)
+            .collect::<Vec<&str>>();
+        if !missing.is_empty() {
+            return Err(AppError::BadRequest(format!("Missing required fields: {}", missing.join(", "))));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResolveDoctorResponse {
+    pub candidates: Vec<DoctorResponse>,
+    pub saved: Option<DoctorResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+    use serde_json::json;
+
+    #[test]
+    fn null_clears_and_missing_keeps() {
+        let patch: DoctorPatchRequest = serde_json::from_value(json!({
+            "date_to": null,
+            "window_days": 14,
+            "weekdays": null,
+            "active": false,
+        })).unwrap();
+
+        assert_eq!(patch.date_to, Some(None));
+        assert_eq!(patch.window_days, Some(Some(14)));
+        assert_eq!(patch.poll_cron, None);
+        assert_eq!(patch.to_update().unwrap(), doc! {
+            "$set": {"window_days": 14_i64, "active": false},
+            "$unset": {"date_to": "", "weekdays": ""},
+        });
+    }
+
+    #[test]
+    fn apply_clears_nulled_fields() {
+        let mut doctor: Doctor = serde_json::from_value(json!({
+            "doctor_ref_id": "doctor-001",
+            "doctor_name": "Nguyen Van A",
+            "subject_ref_id": "subject-001",
+            "subject_name": "Tai Mui Hong",
+            "service_name": "Kham dich vu",
+            "hospital_id": "partner-001",
+            "city_id": "city-hcm",
+            "date_to": "2024-10-14",
+            "poll_cron": "0 */5 * * * *",
+            "current_target": true,
+            "active": true,
+        })).unwrap();
+        let patch: DoctorPatchRequest = serde_json::from_value(json!({"date_to": null, "window_days": 7})).unwrap();
+
+        patch.apply(&mut doctor);
+
+        assert_eq!(doctor.date_to, None);
+        assert_eq!(doctor.window_days, Some(7));
+        assert_eq!(doctor.poll_cron.as_deref(), Some("0 */5 * * * *"));
+    }
+
+    #[test]
+    fn request_names_are_stored_lowercased() {
+        let request: DoctorRequest = serde_json::from_value(json!({
+            "doctor_ref_id": "doctor-001",
+            "doctor_name": "Nguyen Van A",
+            "subject_ref_id": "subject-001",
+            "subject_name": " Tai Mui Hong ",
+            "service_name": "Kham Dich Vu",
+            "hospital_id": "partner-001",
+            "city_id": "city-hcm",
+        })).unwrap();
+
+        let doctor = request.into_doctor();
+
+        assert_eq!(doctor.subject_name, "tai mui hong");
+        assert_eq!(doctor.service_name, "kham dich vu");
+        assert_eq!(doctor.doctor_name, "Nguyen Van A");
+    }
+
+    #[test]
+    fn patched_names_are_stored_lowercased() {
+        let patch: DoctorPatchRequest = serde_json::from_value(json!({
+            "subject_name": "Tai Mui Hong",
+            "service_name": " Kham Dich Vu",
+        })).unwrap();
+
+        assert_eq!(patch.to_update().unwrap(), doc! {
+            "$set": {"subject_name": "tai mui hong", "service_name": "kham dich vu"},
+        });
+        assert_eq!(DoctorPatchRequest::default().subject_name, None);
+    }
+
+    #[test]
+    fn empty_patch_has_no_update() {
+        assert!(DoctorPatchRequest::default().to_update().unwrap().is_empty());
+    }
+
+    #[test]
+    fn resolve_target_rejects_resolved_ids() {
+        let result = serde_json::from_value::<ResolveDoctorRequest>(json!({
+            "doctor_name": "Nguyen Van A",
+            "city_id": "city-hcm",
+            "subject_id": "subject-001",
+            "target": {"window_days": 14, "doctor_ref_id": "doctor-999"},
+        }));
+
+        assert!(result.unwrap_err().to_string().contains("unknown field `doctor_ref_id`"));
+    }
+}
@@ -0,0 +1,68 @@
+use crate::error::AppError;
+use crate::models::doctor_appointment::ChangeType;
+use crate::models::documents::NotificationLedgerEntry;
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// Query string of `GET /med/notifications/ledger`
+#[derive(Debug, Deserialize)]
+pub struct LedgerQuery {
+    pub target_id: Option<String>,
+    /// Notifier name, e.g. `mail`
+    pub channel: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl LedgerQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    pub fn target_id(&self) -> Result<Option<ObjectId>, AppError> {
+        self.target_id.as_deref()
+            .map(|id| ObjectId::parse_str(id).map_err(|_| AppError::BadRequest(format!("Invalid target id: {}", id))))
+            .transpose()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LedgerEntryResponse {
+    pub target_id: String,
+    pub doctor_ref_id: String,
+    pub doctor_name: String,
+    pub appointment_date: String,
+    pub time_id: String,
+    pub start_time: String,
+    pub shift_code: Option<String>,
+    pub channel: String,
+    pub recipient: String,
+    /// Change the latest notification reported
+    pub change_type: ChangeType,
+    pub available_slot: Option<u32>,
+    pub last_notified_at: DateTime<Utc>,
+    pub notify_count: u32,
+}
+
+impl From<NotificationLedgerEntry> for LedgerEntryResponse {
+    fn from(entry: NotificationLedgerEntry) -> Self {
+        LedgerEntryResponse {
+            target_id: entry.target_id.to_hex(),
+            doctor_ref_id: entry.doctor_ref_id,
+            doctor_name: entry.doctor_name,
+            appointment_date: entry.appointment_date,
+            time_id: entry.time_id,
+            start_time: entry.start_time,
+            shift_code: entry.shift_code,
+            channel: entry.channel,
+            recipient: entry.recipient,
+            change_type: entry.change_type,
+            available_slot: entry.available_slot,
+            last_notified_at: DateTime::<Utc>::from(entry.last_notified_at.to_system_time()),
+            notify_count: entry.notify_count,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::models::documents::RunLock;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Body of `GET /med/lock`
+#[derive(Debug, Serialize)]
+pub struct LockResponse {
+    /// Owner id of the instance answering the request
+    pub instance: String,
+    pub lock: Option<LockHolderResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LockHolderResponse {
+    pub owner: String,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// An expired lock is free to be taken by the next scheduled run
+    pub expired: bool,
+    pub held_by_instance: bool,
+}
+
+impl LockResponse {
+    pub fn new(instance: &str, lock: Option<RunLock>) -> LockResponse {
+        let lock = lock.map(|lock| {
+            let expires_at = DateTime::<Utc>::from(lock.expires_at.to_system_time());
+            LockHolderResponse {
+                held_by_instance: lock.owner == instance,
+                owner: lock.owner,
+                acquired_at: DateTime::<Utc>::from(lock.acquired_at.to_system_time()),
+                expires_at,
+                expired: expires_at <= Utc::now(),
+            }
+        });
+
+        LockResponse {
+            instance: instance.to_string(),
+            lock,
+        }
+    }
+}
@@ -1,2 +1,7 @@
 pub mod appointment_model;
-pub mod search_model;
\ No newline at end of file
+pub mod search_model;
+pub mod doctor_model;
+pub mod lock_model;
+pub mod run_model;
+pub mod observation_model;
+pub mod ledger_model;
\ No newline at end of file
@@ -0,0 +1,148 @@
+use crate::error::AppError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How `GET /med/doctors/{id}/availability/{group_by}` buckets observations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AvailabilityGroup {
+    /// Weekday of the appointment date
+    Weekday,
+    ShiftCode,
+    /// Hour of day the slot was observed at
+    Hour,
+}
+
+/// Observation range and timezone shared by the availability and release pattern endpoints
+#[derive(Debug, Deserialize)]
+pub struct ObservationQuery {
+    /// First observation day, `YYYY-MM-DD` in UTC
+    pub from: Option<String>,
+    /// Last observation day, `YYYY-MM-DD` in UTC, inclusive
+    pub to: Option<String>,
+    /// Timezone of weekdays and hours of observation, an Olson name or offset like `+07:00` (default UTC)
+    pub timezone: Option<String>,
+}
+
+impl ObservationQuery {
+    /// The timezone when it looks like one Mongo accepts, unknown Olson names are left to Mongo
+    pub fn timezone(&self) -> Result<String, AppError> {
+        let Some(timezone) = &self.timezone else {
+            return Ok("UTC".to_string());
+        };
+
+        if is_utc_offset(timezone) || is_olson_name(timezone) {
+            Ok(timezone.clone())
+        } else {
+            Err(AppError::BadRequest(format!(
+                "Invalid timezone {}, expected an Olson name like Asia/Ho_Chi_Minh or an offset like +07:00", timezone
+            )))
+        }
+    }
+}
+
+/// `+07`, `+0700` or `+07:00`
+fn is_utc_offset(value: &str) -> bool {
+    let Some(offset) = value.strip_prefix(['+', '-']) else {
+        return false;
+    };
+    let digits = match offset.len() {
+        5 if offset.as_bytes()[2] == b':' => offset.replace(':', ""),
+        2 | 4 => offset.to_string(),
+        _ => return false,
+    };
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+
+    let hours = digits[..2].parse::<u32>().unwrap_or(u32::MAX);
+    let minutes = digits[2..].parse::<u32>().unwrap_or(0);
+    hours <= 23 && minutes <= 59
+}
+
+/// `UTC`, `Asia/Ho_Chi_Minh`, `America/Argentina/Buenos_Aires`, `Etc/GMT+7`
+fn is_olson_name(value: &str) -> bool {
+    value.len() <= 64
+        && value.split('/').all(|part| {
+            part.starts_with(|c: char| c.is_ascii_alphabetic())
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+        })
+}
+
+#[derive(Debug, Serialize)]
+pub struct AvailabilityResponse {
+    pub target_id: String,
+    pub group_by: AvailabilityGroup,
+    pub buckets: Vec<AvailabilityBucket>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AvailabilityBucket {
+    pub key: String,
+    pub observations: u64,
+    /// Observations with at least one free place
+    pub open_observations: u64,
+    pub avg_available_slot: Option<f64>,
+    pub max_available_slot: Option<i64>,
+}
+
+/// Body of `GET /med/doctors/{id}/release-pattern`
+#[derive(Debug, Serialize)]
+pub struct ReleasePatternResponse {
+    pub target_id: String,
+    pub timezone: String,
+    pub observed_from: Option<DateTime<Utc>>,
+    pub observed_to: Option<DateTime<Utc>>,
+    /// Slots seen opening while the target was watched; slots already open at the first observation don't count
+    pub releases: u64,
+    pub by_weekday: Vec<ReleaseBucket>,
+    pub by_hour: Vec<ReleaseBucket>,
+    pub usual_weekday: Option<String>,
+    pub usual_hour: Option<u32>,
+    /// Days between the release and the appointment date
+    pub median_lead_days: Option<i64>,
+    /// Released slots later seen again without free places
+    pub filled: u64,
+    /// Time filled slots stayed open, measured between observations so a lower bound
+    pub median_open_minutes: Option<i64>,
+    /// E.g. "usually Mon at 07:00, about 14 days ahead"
+    pub summary: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReleaseBucket {
+    pub key: String,
+    pub releases: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(timezone: Option<&str>) -> ObservationQuery {
+        ObservationQuery {
+            from: None,
+            to: None,
+            timezone: timezone.map(|timezone| timezone.to_string()),
+        }
+    }
+
+    #[test]
+    fn timezone_defaults_to_utc() {
+        assert_eq!(query(None).timezone().unwrap(), "UTC");
+    }
+
+    #[test]
+    fn olson_names_and_offsets_are_accepted() {
+        for timezone in ["UTC", "Asia/Ho_Chi_Minh", "America/Argentina/Buenos_Aires", "Etc/GMT+7", "+07", "-0330", "+07:00"] {
+            assert_eq!(query(Some(timezone)).timezone().unwrap(), timezone);
+        }
+    }
+
+    #[test]
+    fn malformed_timezones_are_rejected() {
+        for timezone in ["", "+7", "+24:00", "+07:60", "07:00", "Asia/", "/UTC", "Asia/Ho Chi Minh", "$hour", "{\"$x\": 1}"] {
+            assert!(matches!(query(Some(timezone)).timezone(), Err(AppError::BadRequest(_))), "{}", timezone);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::error::AppError;
+use crate::models::documents::{RunOutcome, RunRecord, RunTargetRecord, RunTrigger};
+use chrono::{DateTime, Utc};
+use mongodb::bson;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+/// Query string of `GET /med/runs`
+#[derive(Debug, Deserialize)]
+pub struct RunQuery {
+    /// 1-based
+    pub page: Option<u64>,
+    pub page_size: Option<u64>,
+    /// Only runs that checked the target with this id
+    pub target_id: Option<String>,
+    pub outcome: Option<RunOutcome>,
+}
+
+impl RunQuery {
+    pub fn page(&self) -> u64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn page_size(&self) -> u64 {
+        self.page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// Runs before the requested page, rejected when it doesn't fit the `i64` Mongo expects
+    pub fn skip(&self) -> Result<u64, AppError> {
+        (self.page() - 1).checked_mul(self.page_size())
+            .filter(|skip| *skip <= i64::MAX as u64)
+            .ok_or_else(|| AppError::BadRequest(format!("Page {} is out of range", self.page())))
+    }
+
+    pub fn target_id(&self) -> Result<Option<ObjectId>, AppError> {
+        self.target_id.as_deref()
+            .map(|id| ObjectId::parse_str(id).map_err(|_| AppError::BadRequest(format!("Invalid target id: {}", id))))
+            .transpose()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunPageResponse {
+    pub page: u64,
+    pub page_size: u64,
+    pub total: u64,
+    pub runs: Vec<RunResponse>,
+}
+
+/// Run record with ids as hex strings and times as RFC 3339
+#[derive(Debug, Serialize)]
+pub struct RunResponse {
+    pub id: Option<String>,
+    pub trigger: RunTrigger,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    pub outcome: RunOutcome,
+    pub targets: Vec<RunTargetResponse>,
+    pub slots_found: u64,
+    pub notifications_sent: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunTargetResponse {
+    pub target_id: Option<String>,
+    pub doctor_ref_id: String,
+    pub doctor_name: String,
+    pub upstream_latency_ms: u64,
+    pub slots_found: u64,
+    pub changes: u64,
+    pub notifications_sent: u64,
+    pub error: Option<String>,
+    pub error_code: Option<String>,
+}
+
+impl From<RunRecord> for RunResponse {
+    fn from(run: RunRecord) -> Self {
+        RunResponse {
+            id: run.id.map(|id| id.to_hex()),
+            trigger: run.trigger,
+            started_at: utc(run.started_at),
+            finished_at: utc(run.finished_at),
+            duration_ms: run.finished_at.timestamp_millis() - run.started_at.timestamp_millis(),
+            outcome: run.outcome,
+            targets: run.targets.into_iter().map(RunTargetResponse::from).collect(),
+            slots_found: run.slots_found,
+            notifications_sent: run.notifications_sent,
+            error: run.error,
+        }
+    }
+}
+
+impl From<RunTargetRecord> for RunTargetResponse {
+    fn from(target: RunTargetRecord) -> Self {
+        RunTargetResponse {
+            target_id: target.target_id.map(|id| id.to_hex()),
+            doctor_ref_id: target.doctor_ref_id,
+            doctor_name: target.doctor_name,
+            upstream_latency_ms: target.upstream_latency_ms,
+            slots_found: target.slots_found,
+            changes: target.changes,
+            notifications_sent: target.notifications_sent,
+            error: target.error,
+            error_code: target.error_code,
+        }
+    }
+}
+
+fn utc(datetime: bson::DateTime) -> DateTime<Utc> {
+    DateTime::<Utc>::from(datetime.to_system_time())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(page: Option<u64>, page_size: Option<u64>) -> RunQuery {
+        RunQuery {
+            page,
+            page_size,
+            target_id: None,
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn skip_counts_the_previous_pages() {
+        assert_eq!(query(None, None).skip().unwrap(), 0);
+        assert_eq!(query(Some(0), None).skip().unwrap(), 0);
+        assert_eq!(query(Some(3), Some(10)).skip().unwrap(), 20);
+        assert_eq!(query(Some(2), Some(1000)).skip().unwrap(), MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn huge_page_is_a_bad_request() {
+        assert!(matches!(query(Some(u64::MAX), Some(100)).skip(), Err(AppError::BadRequest(_))));
+        // Fits a u64 but not the i64 Mongo takes
+        assert!(matches!(query(Some(u64::MAX / 100), Some(100)).skip(), Err(AppError::BadRequest(_))));
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchApiResponse {
     pub category: String,
     pub search_key: String,
@@ -10,7 +10,7 @@ pub struct SearchApiResponse {
     pub results: Vec<ResultItem>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hospital {
     pub id: String,
     pub r#type: Option<String>,
@@ -19,14 +19,14 @@ pub struct Hospital {
     pub ctas: Option<Vec<Cta>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct City {
     pub id: Option<String>,
     pub r#type: Option<String>,
     pub name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResultItem {
     pub id: Option<String>,
 
@@ -49,10 +49,10 @@ pub struct ResultItem {
 
     pub price: Option<String>,
 
-    #[serde(rename = "partnerId")]
+    #[serde(rename = "priceDescription")]
     pub price_description: Option<String>,
 
-    #[serde(rename = "partnerId")]
+    #[serde(rename = "treeId")]
     pub tree_id: Option<String>,
 
     pub trees: Option<Vec<Tree>>,
@@ -80,14 +80,14 @@ pub struct ResultItem {
     pub partner: Option<Partner>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tag {
     pub id: Option<String>,
     pub r#type: Option<String>,
     pub name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tree {
     pub tree_id: Option<String>,
     pub detail_shift_id: Option<String>,
@@ -95,14 +95,14 @@ pub struct Tree {
     pub days: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Subject {
     pub id: String,
     pub r#type: Option<String>,
     pub name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Service {
     pub id: String,
 
@@ -121,7 +121,7 @@ pub struct Service {
     pub ctas: Option<Vec<Cta>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cta {
     pub name: Option<String>,
 
@@ -144,12 +144,12 @@ pub struct Cta {
     pub room_id: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Description {
     pub rating: Option<u32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Partner {
     #[serde(rename = "isCashBack")]
     pub is_cash_back: Option<bool>,
@@ -176,4 +176,7 @@ pub struct ApiSearchRequest {
     pub search_key: String,
     pub subject_id: String,
     pub city_id: String,
+    /// Ask the site even when a cached response is available
+    #[serde(default)]
+    pub no_cache: bool,
 }
\ No newline at end of file
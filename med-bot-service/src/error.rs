@@ -0,0 +1,111 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Upstream request failed: {0}")]
+    UpstreamHttp(#[from] reqwest::Error),
+
+    #[error("Upstream {endpoint} response (HTTP {status}) could not be decoded at `{path}`: {message}")]
+    UpstreamDecode {
+        endpoint: String,
+        status: u16,
+        path: String,
+        message: String,
+        /// Truncated copy of the raw body for schema drift diagnostics
+        body: String,
+    },
+
+    #[error("Upstream {endpoint} responded with HTTP {status}")]
+    UpstreamStatus {
+        endpoint: String,
+        status: u16,
+    },
+
+    #[error("Upstream unavailable: {0}")]
+    UpstreamUnavailable(String),
+
+    /// Held back locally while the site's `Retry-After` delay runs
+    #[error("Upstream rate limited: {0}")]
+    UpstreamRateLimited(String),
+
+    #[error("Database error: {0}")]
+    Mongo(#[from] mongodb::error::Error),
+
+    #[error("Mail error: {0}")]
+    Mail(String),
+
+    #[error("Notification failed: {0}")]
+    Notifier(String),
+
+    #[error("No target doctor configured")]
+    NoTarget,
+
+    #[error("Doctor validation failed: {0}")]
+    DoctorValidation(String),
+
+    #[error("Invalid request: {0}")]
+    BadRequest(String),
+
+    #[error("{0} not found")]
+    NotFound(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+/// JSON body of every error response
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl AppError {
+    /// Stable machine readable code, kept unchanged when messages are reworded
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::UpstreamHttp(_) => "upstream_http_error",
+            AppError::UpstreamDecode { .. } => "upstream_decode_error",
+            AppError::UpstreamStatus { .. } => "upstream_status_error",
+            AppError::UpstreamUnavailable(_) => "upstream_circuit_open",
+            AppError::UpstreamRateLimited(_) => "upstream_rate_limited",
+            AppError::Mongo(_) => "database_error",
+            AppError::Mail(_) => "mail_error",
+            AppError::Notifier(_) => "notifier_error",
+            AppError::NoTarget => "no_target_configured",
+            AppError::DoctorValidation(_) => "doctor_validation_failed",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::UpstreamHttp(e) if e.is_timeout() => StatusCode::GATEWAY_TIMEOUT,
+            AppError::UpstreamHttp(_) | AppError::UpstreamDecode { .. } | AppError::UpstreamStatus { .. } => StatusCode::BAD_GATEWAY,
+            AppError::UpstreamUnavailable(_) | AppError::UpstreamRateLimited(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Mail(_) | AppError::Notifier(_) => StatusCode::BAD_GATEWAY,
+            AppError::Mongo(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::NoTarget | AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::DoctorValidation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorResponse {
+            code: self.code(),
+            message: self.to_string(),
+        })
+    }
+}
@@ -0,0 +1,129 @@
+use crate::dto::doctor_model::{DoctorPatchRequest, DoctorQuery, DoctorRequest, DoctorResponse, ResolveDoctorRequest, ResolveDoctorResponse};
+use crate::error::AppError;
+use crate::handlers::json_response;
+use crate::AppState;
+use actix_web::web::{Json, Path, Query};
+use actix_web::{delete, get, patch, post, put, web, HttpResponse};
+use mongodb::bson::oid::ObjectId;
+
+#[post("/med/doctors")]
+async fn create_doctor(data: web::Data<AppState>, doctor_request: Json<DoctorRequest>) -> Result<HttpResponse, AppError> {
+    println!("create_doctor");
+
+    let doctor_service = &data.service.doctor_service;
+    let doctor = doctor_request.into_inner().into_doctor();
+    doctor_service.validate(&doctor)?;
+
+    let doctor = doctor_service.create_doctor(doctor).await?;
+    json_response(HttpResponse::Created(), &DoctorResponse::from(doctor))
+}
+
+#[get("/med/doctors")]
+async fn list_doctors(data: web::Data<AppState>, doctor_query: Query<DoctorQuery>) -> Result<HttpResponse, AppError> {
+    println!("list_doctors");
+
+    let doctors = data.service.doctor_service.list_doctors(&doctor_query).await?;
+    let response = doctors.into_iter()
+        .map(DoctorResponse::from)
+        .collect::<Vec<DoctorResponse>>();
+    json_response(HttpResponse::Ok(), &response)
+}
+
+#[get("/med/doctors/{id}")]
+async fn get_doctor(data: web::Data<AppState>, path: Path<String>) -> Result<HttpResponse, AppError> {
+    println!("get_doctor");
+
+    let doctor = data.service.doctor_service.get_doctor(parse_id(&path)?).await?;
+    json_response(HttpResponse::Ok(), &DoctorResponse::from(doctor))
+}
+
+#[put("/med/doctors/{id}")]
+async fn replace_doctor(data: web::Data<AppState>, path: Path<String>, doctor_request: Json<DoctorRequest>) -> Result<HttpResponse, AppError> {
+    println!("replace_doctor");
+
+    let id = parse_id(&path)?;
+    let doctor_service = &data.service.doctor_service;
+    let doctor = doctor_request.into_inner().into_doctor();
+    doctor_service.validate(&doctor)?;
+
+    let doctor = doctor_service.replace_doctor(id, doctor).await?;
+    json_response(HttpResponse::Ok(), &DoctorResponse::from(doctor))
+}
+
+#[patch("/med/doctors/{id}")]
+async fn patch_doctor(data: web::Data<AppState>, path: Path<String>, patch_request: Json<DoctorPatchRequest>) -> Result<HttpResponse, AppError> {
+    println!("patch_doctor");
+
+    let id = parse_id(&path)?;
+    let doctor = data.service.doctor_service.patch_doctor(id, patch_request.into_inner()).await?;
+    json_response(HttpResponse::Ok(), &DoctorResponse::from(doctor))
+}
+
+#[delete("/med/doctors/{id}")]
+async fn delete_doctor(data: web::Data<AppState>, path: Path<String>) -> Result<HttpResponse, AppError> {
+    println!("delete_doctor");
+
+    data.service.doctor_service.delete_doctor(parse_id(&path)?).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/med/doctors/{id}/activate")]
+async fn activate_doctor(data: web::Data<AppState>, path: Path<String>) -> Result<HttpResponse, AppError> {
+    println!("activate_doctor");
+
+    let doctor = data.service.doctor_service.set_active(parse_id(&path)?, true).await?;
+    json_response(HttpResponse::Ok(), &DoctorResponse::from(doctor))
+}
+
+#[post("/med/doctors/{id}/deactivate")]
+async fn deactivate_doctor(data: web::Data<AppState>, path: Path<String>) -> Result<HttpResponse, AppError> {
+    println!("deactivate_doctor");
+
+    let doctor = data.service.doctor_service.set_active(parse_id(&path)?, false).await?;
+    json_response(HttpResponse::Ok(), &DoctorResponse::from(doctor))
+}
+
+#[post("/med/doctors/{id}/target")]
+async fn set_target_doctor(data: web::Data<AppState>, path: Path<String>) -> Result<HttpResponse, AppError> {
+    println!("set_target_doctor");
+
+    let doctor = data.service.doctor_service.set_current_target(parse_id(&path)?, true).await?;
+    json_response(HttpResponse::Ok(), &DoctorResponse::from(doctor))
+}
+
+#[delete("/med/doctors/{id}/target")]
+async fn unset_target_doctor(data: web::Data<AppState>, path: Path<String>) -> Result<HttpResponse, AppError> {
+    println!("unset_target_doctor");
+
+    let doctor = data.service.doctor_service.set_current_target(parse_id(&path)?, false).await?;
+    json_response(HttpResponse::Ok(), &DoctorResponse::from(doctor))
+}
+
+#[post("/med/doctors/resolve")]
+async fn resolve_doctor(data: web::Data<AppState>, resolve_request: Json<ResolveDoctorRequest>) -> Result<HttpResponse, AppError> {
+    println!("resolve_doctor");
+
+    resolve_request.validate()?;
+    let candidates = data.service.med_service.resolve_doctor(&resolve_request).await?;
+
+    let mut saved = None;
+    if resolve_request.save {
+        let pick = resolve_request.pick.unwrap_or(0);
+        let candidate = candidates.get(pick).cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Candidate at index {}", pick)))?;
+
+        let doctor_service = &data.service.doctor_service;
+        doctor_service.validate(&candidate)?;
+        saved = Some(DoctorResponse::from(doctor_service.create_doctor(candidate).await?));
+    }
+
+    let response = ResolveDoctorResponse {
+        candidates: candidates.into_iter().map(DoctorResponse::from).collect(),
+        saved,
+    };
+    json_response(HttpResponse::Ok(), &response)
+}
+
+fn parse_id(id: &str) -> Result<ObjectId, AppError> {
+    ObjectId::parse_str(id).map_err(|_| AppError::BadRequest(format!("Invalid doctor id: {}", id)))
+}
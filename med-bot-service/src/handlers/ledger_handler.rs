@@ -0,0 +1,18 @@
+use crate::dto::ledger_model::{LedgerEntryResponse, LedgerQuery};
+use crate::error::AppError;
+use crate::handlers::json_response;
+use crate::AppState;
+use actix_web::web::Query;
+use actix_web::{get, web, HttpResponse};
+
+/// When each channel and recipient was last notified about which slot
+#[get("/med/notifications/ledger")]
+async fn list_ledger(data: web::Data<AppState>, ledger_query: Query<LedgerQuery>) -> Result<HttpResponse, AppError> {
+    println!("list_ledger");
+
+    let entries = data.service.ledger_service.list_entries(&ledger_query).await?;
+    let response = entries.into_iter()
+        .map(LedgerEntryResponse::from)
+        .collect::<Vec<LedgerEntryResponse>>();
+    json_response(HttpResponse::Ok(), &response)
+}
@@ -0,0 +1,14 @@
+use crate::dto::lock_model::LockResponse;
+use crate::error::AppError;
+use crate::handlers::json_response;
+use crate::AppState;
+use actix_web::{get, web, HttpResponse};
+
+#[get("/med/lock")]
+async fn get_lock(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    println!("get_lock");
+
+    let lock_service = &data.service.lock_service;
+    let lock = lock_service.current_lock().await?;
+    json_response(HttpResponse::Ok(), &LockResponse::new(lock_service.owner(), lock))
+}
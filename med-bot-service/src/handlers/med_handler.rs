@@ -1,118 +1,74 @@
+use crate::dto::appointment_model::{AnalyzeQuery, ApiAppointmentRequest, AppointmentResponse};
 use crate::dto::search_model::ApiSearchRequest;
+use crate::error::AppError;
+use crate::handlers::json_response;
+use crate::models::documents::{RunContext, RunTrigger};
 use crate::AppState;
-use actix_web::web::Json;
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::web::{Json, Query};
+use actix_web::{get, post, web, HttpResponse};
+use mongodb::bson::DateTime;
 
 #[get("/med/search")]
-async fn search_med(data: web::Data<AppState>, api_search_request: Json<ApiSearchRequest>) -> impl Responder {
+async fn search_med(data: web::Data<AppState>, api_search_request: Json<ApiSearchRequest>) -> Result<HttpResponse, AppError> {
     println!("search_med");
 
-    let result = data.service.med_service
+    let response = data.service.med_service
         .search_med(
-            &data.client,
             api_search_request.search_key.to_owned(),
             api_search_request.city_id.to_owned(),
             api_search_request.subject_id.to_owned(),
+            None,
+            api_search_request.no_cache,
         )
-        .await;
+        .await?;
 
-    match result {
-        Ok(response) => {
-            // Serialize ApiResponse into JSON and return in HttpResponse
-            match serde_json::to_string(&response) {
-                Ok(json_response) => HttpResponse::Ok()
-                    .content_type("application/json")
-                    .body(json_response),
-                Err(e) => {
-                    eprintln!("{:?}", e);
-                    HttpResponse::InternalServerError()
-                        .body("Failed to serialize response to JSON")
-                }
-            }
-        }
-        Err(e) => HttpResponse::InternalServerError()
-            .body(format!("Request failed: {}", e)),
-    }
+    json_response(HttpResponse::Ok(), &response)
 }
 
 #[get("/med/appointments")]
-async fn get_appointments(data: web::Data<AppState>) -> impl Responder {
+async fn get_appointments(data: web::Data<AppState>, api_appointment_request: Query<ApiAppointmentRequest>) -> Result<HttpResponse, AppError> {
     println!("get_appointments");
 
-    let result = data.service.med_service
+    appointments_response(&data, api_appointment_request.into_inner()).await
+}
+
+#[post("/med/appointments")]
+async fn post_appointments(data: web::Data<AppState>, api_appointment_request: Json<ApiAppointmentRequest>) -> Result<HttpResponse, AppError> {
+    println!("post_appointments");
+
+    appointments_response(&data, api_appointment_request.into_inner()).await
+}
+
+async fn appointments_response(data: &web::Data<AppState>, api_appointment_request: ApiAppointmentRequest) -> Result<HttpResponse, AppError> {
+    api_appointment_request.validate()?;
+
+    let response = data.service.med_service
         .get_appointments(
-            &data.client,
-            "test_subject_id".parse().unwrap(),
-            "test_doctor_id".parse().unwrap(),
-            "test_service_id".parse().unwrap(),
-            "test_partner_id".parse().unwrap(),
+            api_appointment_request.subject_id,
+            api_appointment_request.doctor_id,
+            api_appointment_request.service_id,
+            api_appointment_request.partner_id,
+            api_appointment_request.tree_id,
+            None,
+            api_appointment_request.no_cache,
         )
-        .await;
+        .await?;
 
-    match result {
-        Ok(response) => {
-            // Try to deserialize the response into ApiResponse
-            match serde_json::to_string(&response) {
-                Ok(json_response) => HttpResponse::Ok()
-                    .content_type("application/json")
-                    .body(json_response),
-                Err(e) => {
-                    eprintln!("{:?}", e);
-                    HttpResponse::InternalServerError()
-                        .body("Failed to serialize response to JSON")
-                }
-            }
-        }
-        Err(e) => HttpResponse::InternalServerError()
-            .body(format!("Request failed: {}", e)),
-    }
+    // Normalise the upstream schedule before returning it
+    json_response(HttpResponse::Ok(), &AppointmentResponse::from(response))
 }
 
 #[get("/med/appointments/analyze")]
-async fn analyze(data: web::Data<AppState>) -> impl Responder {
+async fn analyze(data: web::Data<AppState>, analyze_query: Query<AnalyzeQuery>) -> Result<HttpResponse, AppError> {
     println!("analyze_med");
 
-    let result = data.service.med_service.analyze_appointment(&data.client).await;
-    match result {
-        Ok(response) => {
-            // Try to deserialize the response into ApiResponse
-            match serde_json::to_string(&response) {
-                Ok(json_response) => HttpResponse::Ok()
-                    .content_type("application/json")
-                    .body(json_response),
-                Err(e) => {
-                    eprintln!("{:?}", e);
-                    HttpResponse::InternalServerError()
-                        .body("Failed to serialize response to JSON")
-                }
-            }
-        }
-        Err(e) => HttpResponse::NotFound()
-            .body(format!("Request unavailable: {}", e)),
-    }
+    let _run_guard = data.service.lock_service.lock_run().await?;
+    let started_at = DateTime::now();
+    let context = RunContext::new(RunTrigger::Api);
+    let analysis = data.service.lock_service
+        .renew_during(data.service.med_service.analyze_appointment(context, analyze_query.no_cache))
+        .await
+        .and_then(|analysis| analysis);
+    data.service.run_service.record_run(context, started_at, &analysis).await;
+    json_response(HttpResponse::Ok(), &analysis?)
 }
-
-#[get("/med/doctor")]
-async fn get_doctor(data: web::Data<AppState>) -> impl Responder {
-    println!("get_doctor");
-
-    let result = data.service.med_service.get_doctor().await;
-    match result {
-        Ok(response) => {
-            // Try to deserialize the response into ApiResponse
-            match serde_json::to_string(&response) {
-                Ok(json_response) => HttpResponse::Ok()
-                    .content_type("application/json")
-                    .body(json_response),
-                Err(e) => {
-                    eprintln!("{:?}", e);
-                    HttpResponse::InternalServerError()
-                        .body("Failed to serialize response to JSON")
-                }
-            }
-        }
-        Err(e) => HttpResponse::NotFound()
-            .body(format!("Request unavailable: {}", e)),
-    }
-}
-
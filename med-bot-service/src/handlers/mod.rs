@@ -1 +1,21 @@
-pub mod med_handler;
\ No newline at end of file
+pub mod med_handler;
+pub mod doctor_handler;
+pub mod scheduler_handler;
+pub mod lock_handler;
+pub mod run_handler;
+pub mod observation_handler;
+pub mod ledger_handler;
+
+use crate::error::AppError;
+use actix_web::{HttpResponse, HttpResponseBuilder};
+use serde::Serialize;
+
+/// Serializes the value as the JSON body of a response with the builder's status
+pub fn json_response<T: Serialize>(mut builder: HttpResponseBuilder, value: &T) -> Result<HttpResponse, AppError> {
+    let json_response = serde_json::to_string(value)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response to JSON: {}", e)))?;
+
+    Ok(builder
+        .content_type("application/json")
+        .body(json_response))
+}
@@ -0,0 +1,50 @@
+use crate::dto::observation_model::{AvailabilityGroup, ObservationQuery, AvailabilityResponse};
+use crate::error::AppError;
+use crate::handlers::json_response;
+use crate::AppState;
+use actix_web::web::{Path, Query};
+use actix_web::{get, web, HttpResponse};
+use mongodb::bson::oid::ObjectId;
+
+/// Slot observations of a target doctor aggregated by weekday, shift code or hour
+#[get("/med/doctors/{id}/availability/{group_by}")]
+async fn get_availability(
+    data: web::Data<AppState>,
+    path: Path<(String, AvailabilityGroup)>,
+    observation_query: Query<ObservationQuery>,
+) -> Result<HttpResponse, AppError> {
+    println!("get_availability");
+
+    let (id, group_by) = path.into_inner();
+    let target_id = ObjectId::parse_str(&id).map_err(|_| AppError::BadRequest(format!("Invalid doctor id: {}", id)))?;
+    // Unknown doctors are reported as such rather than as empty history
+    data.service.doctor_service.get_doctor(target_id).await?;
+
+    let buckets = data.service.observation_service
+        .availability(target_id, group_by, &observation_query)
+        .await?;
+    let response = AvailabilityResponse {
+        target_id: id,
+        group_by,
+        buckets,
+    };
+    json_response(HttpResponse::Ok(), &response)
+}
+
+/// When new slots of a target doctor usually appear and how long they stay open
+#[get("/med/doctors/{id}/release-pattern")]
+async fn get_release_pattern(
+    data: web::Data<AppState>,
+    path: Path<String>,
+    observation_query: Query<ObservationQuery>,
+) -> Result<HttpResponse, AppError> {
+    println!("get_release_pattern");
+
+    let target_id = ObjectId::parse_str(path.as_str()).map_err(|_| AppError::BadRequest(format!("Invalid doctor id: {}", path)))?;
+    data.service.doctor_service.get_doctor(target_id).await?;
+
+    let response = data.service.observation_service
+        .release_pattern(target_id, &observation_query)
+        .await?;
+    json_response(HttpResponse::Ok(), &response)
+}
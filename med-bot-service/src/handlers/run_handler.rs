@@ -0,0 +1,39 @@
+use crate::dto::appointment_model::AnalyzeQuery;
+use crate::dto::run_model::{RunPageResponse, RunQuery, RunResponse};
+use crate::error::AppError;
+use crate::handlers::json_response;
+use crate::models::documents::{RunContext, RunTrigger};
+use crate::AppState;
+use actix_web::web::Query;
+use actix_web::{get, post, web, HttpResponse};
+use mongodb::bson::DateTime;
+
+#[get("/med/runs")]
+async fn list_runs(data: web::Data<AppState>, run_query: Query<RunQuery>) -> Result<HttpResponse, AppError> {
+    println!("list_runs");
+
+    let (runs, total) = data.service.run_service.list_runs(&run_query).await?;
+    let response = RunPageResponse {
+        page: run_query.page(),
+        page_size: run_query.page_size(),
+        total,
+        runs: runs.into_iter().map(RunResponse::from).collect(),
+    };
+    json_response(HttpResponse::Ok(), &response)
+}
+
+/// Analyzes every current target now and returns the run record
+#[post("/med/runs")]
+async fn create_run(data: web::Data<AppState>, analyze_query: Query<AnalyzeQuery>) -> Result<HttpResponse, AppError> {
+    println!("create_run");
+
+    let _run_guard = data.service.lock_service.lock_run().await?;
+    let started_at = DateTime::now();
+    let context = RunContext::new(RunTrigger::Manual);
+    let analysis = data.service.lock_service
+        .renew_during(data.service.med_service.analyze_appointment(context, analyze_query.no_cache))
+        .await
+        .and_then(|analysis| analysis);
+    let run = data.service.run_service.record_run(context, started_at, &analysis).await;
+    json_response(HttpResponse::Created(), &RunResponse::from(run))
+}
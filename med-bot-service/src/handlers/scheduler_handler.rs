@@ -0,0 +1,11 @@
+use crate::error::AppError;
+use crate::handlers::json_response;
+use crate::AppState;
+use actix_web::{get, web, HttpResponse};
+
+#[get("/med/scheduler/status")]
+async fn get_scheduler_status(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    println!("get_scheduler_status");
+
+    json_response(HttpResponse::Ok(), &data.scheduler_monitor.status())
+}
@@ -5,48 +5,102 @@ mod repositories;
 mod config;
 mod services;
 mod dto;
+mod error;
+mod metrics;
+mod adaptive;
+mod resilience;
+mod rate_limit;
+mod cache;
 
 use std::env;
-use crate::config::mongo_config::{MongoClient, MongoClientBuilder};
-use crate::handlers::med_handler;
-use crate::scheduler::start_scheduler;
-use crate::services::med_service;
-use crate::services::med_service::{MedService, MedServiceBuilder};
-use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
+use crate::config::mongo_config::MongoClient;
+use crate::error::AppError;
+use crate::handlers::json_response;
+use crate::metrics::Metrics;
+use crate::rate_limit::RateLimiter;
+use crate::resilience::UpstreamGuard;
+use crate::handlers::{doctor_handler, ledger_handler, lock_handler, med_handler, observation_handler, run_handler, scheduler_handler};
+use crate::scheduler::{start_digest_scheduler, start_scheduler, SchedulerMonitor};
+use crate::services::med_service::MedService;
+use actix_web::{get, web, App, HttpResponse, HttpServer};
 use dotenv::dotenv;
-use lettre::{Message, SmtpTransport, Transport};
-use lettre::message::{MultiPart, SinglePart};
-use lettre::transport::smtp::authentication::Credentials;
 use reqwest::Client;
-use crate::config::mail_config::MailClientBuilder;
-use crate::config::med_target_config::MedTargetBuilder;
-use crate::services::mail_service::MailServiceBuilder;
+use serde_json::json;
+use crate::config::cache_config::CacheConfig;
+use crate::config::digest_config::DigestConfig;
+use crate::config::mail_config::MailClient;
+use crate::config::med_target_config::MedTarget;
+use crate::config::notifier_config::NotifierConfig;
+use crate::config::observation_config::ObservationConfig;
+use crate::config::run_config::RunConfig;
+use crate::config::provider_config::{ProviderConfig, ProviderKind};
+use crate::config::rate_limit_config::RateLimitConfig;
+use crate::config::retry_config::RetryConfig;
+use crate::config::scheduler_config::SchedulerConfig;
+use crate::config::telegram_config::TelegramClient;
+use crate::config::webhook_config::WebhookClient;
+use crate::services::booking_provider::BookingProvider;
+use crate::services::digest_service::DigestService;
+use crate::services::doctor_service::DoctorService;
+use crate::services::ledger_service::LedgerService;
+use crate::services::lock_service::LockService;
+use crate::services::run_service::RunService;
+use crate::services::http_provider::HttpBookingProvider;
+use crate::services::mail_service::MailService;
+use crate::services::mock_provider::MockBookingProvider;
+use crate::services::observation_service::ObservationService;
+use crate::services::snapshot_service::SnapshotService;
+use crate::services::telegram_service::TelegramService;
+use crate::services::webhook_service::WebhookService;
+use std::sync::Arc;
 
 #[get("/healthz")]
-async fn health() -> impl Responder {
-    "Up".to_string()
+async fn health(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    let scheduler = data.scheduler_monitor.status();
+    let (builder, status) = if scheduler.degraded {
+        (HttpResponse::ServiceUnavailable(), "Degraded")
+    } else {
+        (HttpResponse::Ok(), "Up")
+    };
+
+    json_response(builder, &json!({
+        "status": status,
+        "scheduler": scheduler,
+        "upstream": data.service.med_service.circuit_status(),
+    }))
 }
 
 #[get("/ipz")]
-async fn get_ips() -> impl Responder {
-    let result = reqwest::get("https://httpbin.org/ip")
-        .await;
-    match result {
-        Ok(response) => {
-            HttpResponse::Ok().content_type("application/json").body(response.text().await.unwrap())
-        }
-        Err(error) => HttpResponse::InternalServerError().body(error.to_string()),
-    }
+async fn get_ips() -> Result<HttpResponse, AppError> {
+    let response = reqwest::get("https://httpbin.org/ip")
+        .await?;
+    Ok(HttpResponse::Ok().content_type("application/json").body(response.text().await?))
+}
+
+#[get("/metrics")]
+async fn get_metrics(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    let snapshot = data.metrics.snapshot()
+        .with_upstream_budget(data.rate_limiter.status());
+    json_response(HttpResponse::Ok(), &snapshot)
 }
 
 struct AppState {
-    client: Client,
-    mongo_client: MongoClient,
+    metrics: Arc<Metrics>,
+    rate_limiter: Arc<RateLimiter>,
+    scheduler_config: SchedulerConfig,
+    scheduler_monitor: SchedulerMonitor,
     service: ServiceState,
 }
 
 struct ServiceState {
     med_service: MedService,
+    doctor_service: DoctorService,
+    lock_service: LockService,
+    run_service: RunService,
+    observation_service: ObservationService,
+    ledger_service: LedgerService,
+    /// Set when the `digest` channel is enabled
+    digest_service: Option<DigestService>,
 }
 
 #[actix_web::main]
@@ -69,34 +123,163 @@ async fn main() -> std::io::Result<()> {
     log::info!("Starting HTTP server: go to http://0.0.0.0:8082");
 
     // Config
-    let mongo_client = MongoClientBuilder::new().await
-        .with_dynamic_collection()
-        .with_user_collection()
+    let mongo_client = MongoClient::builder().await
         .with_doctor_collection()
+        .with_slot_snapshot_collection()
+        .with_run_lock_collection()
+        .with_run_record_collection()
+        .with_slot_observation_collection()
+        .with_notification_ledger_collection()
+        .with_digest_item_collection()
+        .build();
+
+    let med_target = MedTarget::builder()
+        .build();
+    let header_profiles = med_target.header_profiles.clone();
+
+    let provider_config = ProviderConfig::builder()
+        .build();
+
+    let retry_config = RetryConfig::builder()
         .build();
 
-    let mail_client = MailClientBuilder::new()
+    let rate_limit_config = RateLimitConfig::builder()
         .build();
 
-    let med_target = MedTargetBuilder::new()
+    let cache_config = CacheConfig::builder()
         .build();
 
+    let notifier_config = NotifierConfig::builder()
+        .build();
+
+    let scheduler_config = SchedulerConfig::builder()
+        .build();
+
+    let observation_config = ObservationConfig::builder()
+        .build();
+
+    let run_config = RunConfig::builder()
+        .build();
+
+    let metrics = Arc::new(Metrics::default());
+    let rate_limiter = Arc::new(RateLimiter::new(&rate_limit_config));
+
     // Service
-    let mail_service = MailServiceBuilder::new(mail_client)
+    let snapshot_service = SnapshotService::builder(mongo_client.slot_snapshot_collection.clone())
+        .build();
+    if let Err(e) = snapshot_service.ensure_indexes().await {
+        log::error!("Failed to create slot snapshot indexes: {}", e);
+    }
+
+    let observation_service = ObservationService::builder(mongo_client.slot_observation_collection.clone())
         .build();
+    if let Err(e) = observation_service.ensure_indexes(observation_config.retention).await {
+        log::error!("Failed to create slot observation indexes: {}", e);
+    }
+
+    let ledger_service = LedgerService::builder(mongo_client.notification_ledger_collection.clone(), notifier_config.cooldown)
+        .build();
+    if let Err(e) = ledger_service.ensure_indexes(notifier_config.ledger_retention).await {
+        log::error!("Failed to create notification ledger indexes: {}", e);
+    }
+
+    let booking_provider: Arc<dyn BookingProvider> = match provider_config.kind {
+        ProviderKind::Http => Arc::new(HttpBookingProvider::builder(Client::new(), med_target.clone())
+            .with_metrics(metrics.clone())
+            .with_rate_limiter(rate_limiter.clone())
+            .build()),
+        ProviderKind::Mock => Arc::new(MockBookingProvider::builder(&provider_config.fixture_dir)
+            .with_metrics(metrics.clone())
+            .build()),
+    };
+    log::info!("Booking provider: {}", booking_provider.name());
 
-    let med_service = MedServiceBuilder::new(
+    let mut med_service_builder = MedService::builder(
         med_target,
         mongo_client.doctor_collection.clone(),
-        mail_service.clone(),
     )
+        .with_booking_provider(booking_provider)
+        .with_upstream_guard(UpstreamGuard::new(&retry_config))
+        .with_metrics(metrics.clone())
+        .with_cache_ttl(cache_config.ttl)
+        .with_snapshot_service(snapshot_service)
+        .with_observation_service(observation_service.clone())
+        .with_ledger_service(ledger_service.clone());
+
+    // Notification channels
+    let notifier_client = Client::new();
+    if notifier_config.is_enabled("mail") {
+        let mail_client = MailClient::builder()
+            .build();
+        let mail_service = MailService::builder(mail_client)
+            .build();
+        med_service_builder = med_service_builder.with_notifier(Arc::new(mail_service));
+    }
+    if notifier_config.is_enabled("telegram") {
+        let telegram_client = TelegramClient::builder()
+            .build();
+        let telegram_service = TelegramService::builder(notifier_client.clone(), telegram_client)
+            .build();
+        med_service_builder = med_service_builder.with_notifier(Arc::new(telegram_service));
+    }
+    if notifier_config.is_enabled("webhook") {
+        let webhook_client = WebhookClient::builder()
+            .build();
+        let webhook_service = WebhookService::builder(notifier_client.clone(), webhook_client)
+            .build();
+        med_service_builder = med_service_builder.with_notifier(Arc::new(webhook_service));
+    }
+    let mut digest_service = None;
+    if notifier_config.is_enabled("digest") {
+        let mail_client = MailClient::builder()
+            .build();
+        let mail_service = MailService::builder(mail_client)
+            .build();
+        let digest_config = DigestConfig::builder()
+            .build();
+        let service = DigestService::builder(mongo_client.digest_item_collection.clone(), mail_service, digest_config)
+            .build();
+        if let Err(e) = service.ensure_indexes().await {
+            log::error!("Failed to create digest item indexes: {}", e);
+        }
+        med_service_builder = med_service_builder.with_notifier(Arc::new(service.clone()));
+        digest_service = Some(service);
+    }
+    log::info!("Notification channels: {:?}", notifier_config.channels);
+
+    let med_service = med_service_builder
+        .build();
+
+    let doctor_service = DoctorService::builder(mongo_client.doctor_collection.clone())
+        .with_header_profiles(header_profiles)
+        .build();
+    if let Err(e) = doctor_service.ensure_indexes().await {
+        log::error!("Failed to create doctor indexes: {}", e);
+    }
+
+    let lock_service = LockService::builder(mongo_client.run_lock_collection.clone(), scheduler_config.lock_ttl)
         .build();
+    log::info!("Scheduler lock owner: {}", lock_service.owner());
+
+    let run_service = RunService::builder(mongo_client.run_record_collection.clone())
+        .build();
+    if let Err(e) = run_service.ensure_indexes(run_config.retention).await {
+        log::error!("Failed to create run record indexes: {}", e);
+    }
 
     let app_state = web::Data::new(AppState {
-        client: Client::new(),
-        mongo_client,
+        metrics,
+        rate_limiter,
+        scheduler_monitor: SchedulerMonitor::new(&scheduler_config),
+        scheduler_config,
         service: ServiceState {
             med_service,
+            doctor_service,
+            lock_service,
+            run_service,
+            observation_service,
+            ledger_service,
+            digest_service,
         },
     });
 
@@ -107,16 +290,50 @@ async fn main() -> std::io::Result<()> {
         start_scheduler(scheduler_state).await;
     });
 
+    let digest_state = app_state.clone();
+    actix_rt::spawn(async move {
+        start_digest_scheduler(digest_state).await;
+    });
+
     HttpServer::new(move || {
         let state_clone = app_state.clone();
         App::new()
             .app_data(state_clone)
+            // Report malformed bodies and query strings with the same JSON error shape
+            .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                AppError::BadRequest(err.to_string()).into()
+            }))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                AppError::BadRequest(err.to_string()).into()
+            }))
+            .app_data(web::PathConfig::default().error_handler(|err, _| {
+                AppError::BadRequest(err.to_string()).into()
+            }))
             .service(health)
             .service(get_ips)
+            .service(get_metrics)
             .service(med_handler::search_med)
             .service(med_handler::get_appointments)
+            .service(med_handler::post_appointments)
             .service(med_handler::analyze)
-            .service(med_handler::get_doctor)
+            .service(scheduler_handler::get_scheduler_status)
+            .service(lock_handler::get_lock)
+            .service(run_handler::list_runs)
+            .service(run_handler::create_run)
+            .service(observation_handler::get_availability)
+            .service(observation_handler::get_release_pattern)
+            .service(ledger_handler::list_ledger)
+            .service(doctor_handler::resolve_doctor)
+            .service(doctor_handler::create_doctor)
+            .service(doctor_handler::list_doctors)
+            .service(doctor_handler::get_doctor)
+            .service(doctor_handler::replace_doctor)
+            .service(doctor_handler::patch_doctor)
+            .service(doctor_handler::delete_doctor)
+            .service(doctor_handler::activate_doctor)
+            .service(doctor_handler::deactivate_doctor)
+            .service(doctor_handler::set_target_doctor)
+            .service(doctor_handler::unset_target_doctor)
     })
         .bind(("0.0.0.0", 8082))?
         .run()
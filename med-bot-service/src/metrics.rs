@@ -0,0 +1,54 @@
+use crate::rate_limit::RateLimitStatus;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// In-process counters exposed on `/metrics`
+#[derive(Debug, Default)]
+pub struct Metrics {
+    decode_failures: Mutex<BTreeMap<String, u64>>,
+    cache_hits: Mutex<BTreeMap<String, u64>>,
+    cache_misses: Mutex<BTreeMap<String, u64>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsSnapshot {
+    /// Upstream responses that no longer match our DTOs, per endpoint
+    pub decode_failures: BTreeMap<String, u64>,
+    /// Upstream responses served from the cache, per endpoint
+    pub cache_hits: BTreeMap<String, u64>,
+    /// Cacheable upstream calls that had to go to the site, per endpoint
+    pub cache_misses: BTreeMap<String, u64>,
+    /// Booking site request budget shared by the HTTP provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_budget: Option<RateLimitStatus>,
+}
+
+impl MetricsSnapshot {
+    pub fn with_upstream_budget(mut self, upstream_budget: RateLimitStatus) -> MetricsSnapshot {
+        self.upstream_budget = Some(upstream_budget);
+        self
+    }
+}
+
+impl Metrics {
+    pub fn record_decode_failure(&self, endpoint: &str) {
+        let mut decode_failures = self.decode_failures.lock().unwrap();
+        *decode_failures.entry(endpoint.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn record_cache_lookup(&self, endpoint: &str, hit: bool) {
+        let counters = if hit { &self.cache_hits } else { &self.cache_misses };
+        let mut counters = counters.lock().unwrap();
+        *counters.entry(endpoint.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            decode_failures: self.decode_failures.lock().unwrap().clone(),
+            cache_hits: self.cache_hits.lock().unwrap().clone(),
+            cache_misses: self.cache_misses.lock().unwrap().clone(),
+            upstream_budget: None,
+        }
+    }
+}
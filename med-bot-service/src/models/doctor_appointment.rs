@@ -1,4 +1,7 @@
 use crate::dto::appointment_model::{DoctorChangeInfo, TimeSlot};
+use crate::models::documents::Doctor;
+use chrono::{Days, NaiveDate, NaiveTime, Weekday};
+use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug)]
@@ -14,6 +17,311 @@ pub struct AppointmentPicking {
     pub doctor_name: Option<String>,
     pub appointment_day: Option<String>,
     pub appointment_date: Option<String>,
+    pub shift_code: Option<String>,
+    pub shift_name: Option<String>,
     pub available_slot: Option<Vec<TimeSlot>>,
     pub doctor_change_info: Option<DoctorChangeInfo>,
-}
\ No newline at end of file
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct TargetAnalysis {
+    /// `_id` of the target document, kept for run records
+    #[serde(skip)]
+    pub target_id: Option<ObjectId>,
+    pub doctor_ref_id: String,
+    pub doctor_name: String,
+    pub appointments: Vec<AppointmentPicking>,
+    pub changes: Vec<AppointmentChange>,
+    /// Time spent waiting on the search and appointment APIs
+    pub upstream_latency_ms: u64,
+    /// Channels that delivered the change notification
+    pub notifications_sent: usize,
+    pub error: Option<String>,
+    pub error_code: Option<String>,
+}
+
+impl TargetAnalysis {
+    /// Matching time slots over all days and shifts
+    pub fn slots_found(&self) -> usize {
+        self.appointments.iter()
+            .filter_map(|appointment| appointment.available_slot.as_ref())
+            .map(|slots| slots.len())
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeType {
+    Opened,
+    CountChanged,
+    Closed,
+}
+
+impl ChangeType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChangeType::Opened => "New slots opened",
+            ChangeType::CountChanged => "Available places changed",
+            ChangeType::Closed => "Slots no longer available",
+        }
+    }
+}
+
+/// Slots of one date and shift that changed in the same way since the previous snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppointmentChange {
+    pub change_type: ChangeType,
+    pub appointment: AppointmentPicking,
+}
+
+/// Matching rules resolved from a target `Doctor` document
+#[derive(Debug, Clone)]
+pub struct AppointmentWindow {
+    pub date_from: NaiveDate,
+    pub date_to: NaiveDate,
+    pub weekdays: Option<Vec<Weekday>>,
+    pub earliest_time: Option<NaiveTime>,
+    pub latest_time: Option<NaiveTime>,
+    pub min_available_slot: u32,
+}
+
+impl AppointmentWindow {
+    pub fn from_doctor(doctor: &Doctor, today: NaiveDate) -> Result<AppointmentWindow, String> {
+        let target_date = parse_optional_date("target_date", doctor.target_date.as_deref())?;
+        let date_from = parse_optional_date("date_from", doctor.date_from.as_deref())?;
+        let date_to = parse_optional_date("date_to", doctor.date_to.as_deref())?;
+
+        let date_from = date_from.or(target_date).unwrap_or(today);
+        let date_to = match date_to.or(target_date) {
+            Some(date_to) => date_to,
+            None => match doctor.window_days {
+                Some(window_days) => today.checked_add_days(Days::new(window_days as u64))
+                    .ok_or("window_days is out of range")?,
+                None => return Err("No target date, date_to or window_days configured".to_string()),
+            },
+        };
+
+        if date_to < date_from {
+            return Err(format!("Date window is empty: {} is after {}", date_from, date_to));
+        }
+
+        let weekdays = match &doctor.weekdays {
+            Some(weekdays) => Some(weekdays.iter()
+                .map(|day| day.parse::<Weekday>().map_err(|_| format!("Invalid weekday: {}", day)))
+                .collect::<Result<Vec<Weekday>, String>>()?),
+            None => None,
+        };
+
+        let earliest_time = parse_optional_time("earliest_time", doctor.earliest_time.as_deref())?;
+        let latest_time = parse_optional_time("latest_time", doctor.latest_time.as_deref())?;
+
+        Ok(AppointmentWindow {
+            date_from,
+            date_to,
+            weekdays,
+            earliest_time,
+            latest_time,
+            min_available_slot: doctor.min_available_slot.unwrap_or(1).max(1),
+        })
+    }
+
+    pub fn contains_date(&self, date: NaiveDate) -> bool {
+        use chrono::Datelike;
+
+        date >= self.date_from
+            && date <= self.date_to
+            && self.weekdays.as_ref().is_none_or(|weekdays| weekdays.contains(&date.weekday()))
+    }
+
+    /// A slot matches when its start time is inside the time-of-day bounds and it has enough free places
+    pub fn accepts_slot(&self, slot: &TimeSlot) -> bool {
+        let (Some(available_slot), Some(max_slot)) = (slot.available_slot, slot.max_slot) else {
+            return false;
+        };
+        if available_slot < self.min_available_slot || available_slot > max_slot {
+            return false;
+        }
+
+        if self.earliest_time.is_none() && self.latest_time.is_none() {
+            return true;
+        }
+
+        match parse_slot_time(&slot.start_time) {
+            Some(start_time) => self.earliest_time.is_none_or(|earliest| start_time >= earliest)
+                && self.latest_time.is_none_or(|latest| start_time <= latest),
+            None => false,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        if self.date_from == self.date_to {
+            self.date_from.format("%Y-%m-%d").to_string()
+        } else {
+            format!("{} - {}", self.date_from.format("%Y-%m-%d"), self.date_to.format("%Y-%m-%d"))
+        }
+    }
+}
+
+pub fn parse_slot_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(value.trim(), "%H:%M:%S"))
+        .ok()
+}
+
+fn parse_optional_date(field: &str, value: Option<&str>) -> Result<Option<NaiveDate>, String> {
+    match value {
+        Some(value) if !value.trim().is_empty() => NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| format!("Invalid {}: {}, expected %Y-%m-%d", field, value)),
+        _ => Ok(None),
+    }
+}
+
+fn parse_optional_time(field: &str, value: Option<&str>) -> Result<Option<NaiveTime>, String> {
+    match value {
+        Some(value) if !value.trim().is_empty() => parse_slot_time(value)
+            .map(Some)
+            .ok_or_else(|| format!("Invalid {}: {}, expected %H:%M", field, value)),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn doctor(rules: serde_json::Value) -> Doctor {
+        let mut document = json!({
+            "doctor_ref_id": "doctor-001",
+            "doctor_name": "Nguyen Van A",
+            "subject_ref_id": "subject-001",
+            "subject_name": "Tai Mui Hong",
+            "service_name": "Kham dich vu",
+            "hospital_id": "partner-001",
+            "city_id": "city-hcm",
+            "current_target": true,
+            "active": true,
+        });
+        document.as_object_mut().unwrap().extend(rules.as_object().unwrap().clone());
+        serde_json::from_value(document).unwrap()
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn slot(start_time: &str, available_slot: u32, max_slot: u32) -> TimeSlot {
+        TimeSlot {
+            time_id: "slot-001".to_string(),
+            available_slot: Some(available_slot),
+            max_slot: Some(max_slot),
+            start_time: start_time.to_string(),
+            end_time: "23:59".to_string(),
+            room_id: "room-001".to_string(),
+            priority_room: 0,
+        }
+    }
+
+    #[test]
+    fn window_includes_both_edges() {
+        let window = AppointmentWindow::from_doctor(
+            &doctor(json!({"date_from": "2024-10-02", "date_to": "2024-10-04"})),
+            date("2024-10-01"),
+        ).unwrap();
+
+        assert!(!window.contains_date(date("2024-10-01")));
+        assert!(window.contains_date(date("2024-10-02")));
+        assert!(window.contains_date(date("2024-10-04")));
+        assert!(!window.contains_date(date("2024-10-05")));
+    }
+
+    #[test]
+    fn window_days_start_today() {
+        let window = AppointmentWindow::from_doctor(&doctor(json!({"window_days": 7})), date("2024-10-01")).unwrap();
+
+        assert_eq!(window.date_from, date("2024-10-01"));
+        assert_eq!(window.date_to, date("2024-10-08"));
+        assert!(window.contains_date(date("2024-10-08")));
+    }
+
+    #[test]
+    fn target_date_is_a_single_day_window() {
+        let window = AppointmentWindow::from_doctor(&doctor(json!({"target_date": "2024-10-02"})), date("2024-10-01")).unwrap();
+
+        assert_eq!(window.label(), "2024-10-02");
+        assert!(window.contains_date(date("2024-10-02")));
+        assert!(!window.contains_date(date("2024-10-03")));
+    }
+
+    #[test]
+    fn window_needs_an_end() {
+        assert!(AppointmentWindow::from_doctor(&doctor(json!({})), date("2024-10-01")).is_err());
+        assert!(AppointmentWindow::from_doctor(
+            &doctor(json!({"date_from": "2024-10-05", "date_to": "2024-10-04"})),
+            date("2024-10-01"),
+        ).is_err());
+    }
+
+    #[test]
+    fn weekdays_filter_dates() {
+        // 2024-10-07 is a Monday
+        let window = AppointmentWindow::from_doctor(
+            &doctor(json!({"date_from": "2024-10-07", "date_to": "2024-10-13", "weekdays": ["Mon", "Sat"]})),
+            date("2024-10-01"),
+        ).unwrap();
+
+        let matching = window.date_from.iter_days()
+            .take_while(|day| *day <= window.date_to)
+            .filter(|day| window.contains_date(*day))
+            .collect::<Vec<NaiveDate>>();
+        assert_eq!(matching, vec![date("2024-10-07"), date("2024-10-12")]);
+    }
+
+    #[test]
+    fn invalid_weekday_is_rejected() {
+        let result = AppointmentWindow::from_doctor(
+            &doctor(json!({"window_days": 7, "weekdays": ["Someday"]})),
+            date("2024-10-01"),
+        );
+
+        assert_eq!(result.unwrap_err(), "Invalid weekday: Someday");
+    }
+
+    #[test]
+    fn time_bounds_are_inclusive() {
+        let window = AppointmentWindow::from_doctor(
+            &doctor(json!({"window_days": 7, "earliest_time": "08:00", "latest_time": "11:30"})),
+            date("2024-10-01"),
+        ).unwrap();
+
+        assert!(!window.accepts_slot(&slot("07:59", 1, 4)));
+        assert!(window.accepts_slot(&slot("08:00", 1, 4)));
+        assert!(window.accepts_slot(&slot("11:30:00", 1, 4)));
+        assert!(!window.accepts_slot(&slot("11:31", 1, 4)));
+        assert!(!window.accepts_slot(&slot("noon", 1, 4)));
+    }
+
+    #[test]
+    fn min_available_slot_defaults_to_one() {
+        let window = AppointmentWindow::from_doctor(&doctor(json!({"window_days": 7})), date("2024-10-01")).unwrap();
+
+        assert_eq!(window.min_available_slot, 1);
+        assert!(!window.accepts_slot(&slot("08:00", 0, 4)));
+        assert!(window.accepts_slot(&slot("08:00", 1, 4)));
+    }
+
+    #[test]
+    fn min_available_slot_filters_slots() {
+        let window = AppointmentWindow::from_doctor(
+            &doctor(json!({"window_days": 7, "min_available_slot": 2})),
+            date("2024-10-01"),
+        ).unwrap();
+
+        assert!(!window.accepts_slot(&slot("08:00", 1, 4)));
+        assert!(window.accepts_slot(&slot("08:00", 2, 4)));
+        // More free places than the slot holds means the upstream data is off
+        assert!(!window.accepts_slot(&slot("08:00", 5, 4)));
+    }
+}
@@ -1,16 +1,9 @@
+use crate::models::doctor_appointment::{AppointmentChange, AppointmentPicking, ChangeType};
 use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct User {
-    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
-    pub id: Option<ObjectId>,
-    pub name: String,
-    pub location: String,
-    pub title: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Doctor {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
@@ -21,7 +14,180 @@ pub struct Doctor {
     pub service_name: String,
     pub hospital_id: String,
     pub city_id: String,
-    pub target_date: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_date: Option<String>,
+    /// First day (`%Y-%m-%d`) of the accepted appointment window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_from: Option<String>,
+    /// Last day (`%Y-%m-%d`) of the accepted appointment window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_to: Option<String>,
+    /// Rolling window of days from today, used when `date_to` is not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_days: Option<u32>,
+    /// Allowed weekdays, e.g. `["Mon", "Tue"]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekdays: Option<Vec<String>>,
+    /// Earliest accepted slot start time (`%H:%M`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub earliest_time: Option<String>,
+    /// Latest accepted slot start time (`%H:%M`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_time: Option<String>,
+    /// Minimum free places a slot must have to be reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_available_slot: Option<u32>,
+    /// Cron expression polling this target instead of the default schedule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_cron: Option<String>,
+    /// Request header profile used for this target instead of the default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_profile: Option<String>,
     pub current_target: bool,
     pub active: bool,
-}
\ No newline at end of file
+}
+
+/// Slots found for a target in its latest analysis run, compared with the next run to detect changes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SlotSnapshot {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub target_id: ObjectId,
+    pub doctor_ref_id: String,
+    pub taken_at: DateTime,
+    pub appointments: Vec<AppointmentPicking>,
+}
+
+/// Lease taken by the scheduler before a run, so only one replica polls at a time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunLock {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub owner: String,
+    pub acquired_at: DateTime,
+    pub expires_at: DateTime,
+}
+
+/// What started an analysis run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunTrigger {
+    Cron,
+    /// `POST /med/runs`
+    Manual,
+    /// `GET /med/appointments/analyze`
+    Api,
+}
+
+/// The run an analysis belongs to, known before its `RunRecord` is stored under `id`
+#[derive(Debug, Clone, Copy)]
+pub struct RunContext {
+    pub id: ObjectId,
+    pub trigger: RunTrigger,
+}
+
+impl RunContext {
+    pub fn new(trigger: RunTrigger) -> RunContext {
+        RunContext {
+            id: ObjectId::new(),
+            trigger,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    Success,
+    /// Some targets were checked, others failed
+    Partial,
+    Failed,
+}
+
+/// What one analysis run did, kept as history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub trigger: RunTrigger,
+    pub started_at: DateTime,
+    pub finished_at: DateTime,
+    pub outcome: RunOutcome,
+    pub targets: Vec<RunTargetRecord>,
+    pub slots_found: u64,
+    pub notifications_sent: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunTargetRecord {
+    pub target_id: Option<ObjectId>,
+    pub doctor_ref_id: String,
+    pub doctor_name: String,
+    pub upstream_latency_ms: u64,
+    pub slots_found: u64,
+    pub changes: u64,
+    pub notifications_sent: u64,
+    pub error: Option<String>,
+    pub error_code: Option<String>,
+}
+
+/// One upstream time slot as seen by one analysis run, kept for availability history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotObservation {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub target_id: ObjectId,
+    pub doctor_ref_id: String,
+    /// Retention is counted from here by the TTL index
+    pub observed_at: DateTime,
+    /// `YYYY-MM-DD`
+    pub appointment_date: String,
+    /// Weekday of `appointment_date`, e.g. `Mon`
+    pub appointment_weekday: String,
+    pub shift_code: Option<String>,
+    pub shift_name: Option<String>,
+    pub time_id: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub available_slot: Option<u32>,
+    pub max_slot: Option<u32>,
+}
+
+/// Last notification about one slot on one channel, used to hold back repeats within the cooldown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationLedgerEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub target_id: ObjectId,
+    pub doctor_ref_id: String,
+    pub doctor_name: String,
+    pub appointment_date: String,
+    pub time_id: String,
+    pub channel: String,
+    pub recipient: String,
+    pub change_type: ChangeType,
+    pub shift_code: Option<String>,
+    pub start_time: String,
+    pub available_slot: Option<u32>,
+    pub last_notified_at: DateTime,
+    pub notify_count: u32,
+}
+
+/// Change held for the next digest email instead of being sent right away
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestItem {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub doctor_ref_id: String,
+    pub doctor_name: String,
+    pub change: AppointmentChange,
+    pub found_at: DateTime,
+    /// Set once a digest run claimed the item, so it is never picked up by another one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sending_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claim_id: Option<ObjectId>,
+    /// Unset until a digest including the item went out
+    pub sent_at: Option<DateTime>,
+}
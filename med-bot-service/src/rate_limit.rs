@@ -0,0 +1,281 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use crate::config::rate_limit_config::RateLimitConfig;
+use crate::error::AppError;
+
+/// Longest `Retry-After` we accept, a larger one is most likely a misconfigured site
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(15 * 60);
+const USAGE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Booking site request budget exposed on `/metrics`
+#[derive(Debug, Clone, Serialize)]
+pub struct RateLimitStatus {
+    pub requests_per_minute: u32,
+    pub max_concurrency: usize,
+    pub in_flight: usize,
+    pub available_tokens: f64,
+    pub requests_last_minute: usize,
+    /// `requests_last_minute` over `requests_per_minute`
+    pub budget_used: f64,
+    pub total_requests: u64,
+    /// Requests that had to wait for a token
+    pub throttled_requests: u64,
+    pub retry_after_deferrals: u64,
+    /// Requests refused at once while a `Retry-After` delay was running
+    pub blocked_requests: u64,
+    pub blocked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    /// Set from a `Retry-After` header, no request leaves before it
+    blocked_until: Option<Instant>,
+    /// Start times of the requests sent within the last minute
+    recent: VecDeque<Instant>,
+    total_requests: u64,
+    throttled_requests: u64,
+    retry_after_deferrals: u64,
+    blocked_requests: u64,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant, tokens_per_sec: f64, capacity: f64) {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * tokens_per_sec).min(capacity);
+        self.refilled_at = now;
+
+        while self.recent.front().is_some_and(|sent_at| now.saturating_duration_since(*sent_at) >= USAGE_WINDOW) {
+            self.recent.pop_front();
+        }
+    }
+
+    /// What is left of the `Retry-After` delay, `None` when there is none
+    fn blocked_for(&self, now: Instant) -> Option<Duration> {
+        self.blocked_until
+            .filter(|blocked_until| *blocked_until > now)
+            .map(|blocked_until| blocked_until - now)
+    }
+
+    /// Refuses the request while a `Retry-After` delay runs
+    fn check_blocked(&mut self, now: Instant) -> Result<(), AppError> {
+        match self.blocked_for(now) {
+            Some(remaining) => {
+                self.blocked_requests += 1;
+                Err(AppError::UpstreamRateLimited(format!(
+                    "booking site asked to retry after {}s", remaining.as_secs_f64().ceil()
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// How long until the next token, `None` when one is available now
+    fn wait_time(&self, tokens_per_sec: f64) -> Option<Duration> {
+        if self.tokens >= 1.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64((1.0 - self.tokens) / tokens_per_sec))
+    }
+}
+
+/// Token bucket shared by every request to the booking site, refilled at `requests_per_minute`
+/// and holding at most `max_concurrency` tokens, plus a cap on requests in flight
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_minute: u32,
+    max_concurrency: usize,
+    permits: Semaphore,
+    bucket: Mutex<Bucket>,
+}
+
+/// Held for the whole request, releases its concurrency slot when dropped
+pub struct RatePermit<'a> {
+    _permit: SemaphorePermit<'a>,
+}
+
+impl RateLimiter {
+    pub fn new(rate_limit_config: &RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            requests_per_minute: rate_limit_config.requests_per_minute,
+            max_concurrency: rate_limit_config.max_concurrency,
+            permits: Semaphore::new(rate_limit_config.max_concurrency),
+            bucket: Mutex::new(Bucket {
+                tokens: rate_limit_config.max_concurrency as f64,
+                refilled_at: Instant::now(),
+                blocked_until: None,
+                recent: VecDeque::new(),
+                total_requests: 0,
+                throttled_requests: 0,
+                retry_after_deferrals: 0,
+                blocked_requests: 0,
+            }),
+        }
+    }
+
+    fn tokens_per_sec(&self) -> f64 {
+        self.requests_per_minute as f64 / 60.0
+    }
+
+    /// Waits for a free concurrency slot, then for a token. Fails at once while the site's
+    /// `Retry-After` delay runs, instead of holding a slot and the run lock for up to `MAX_RETRY_AFTER`.
+    pub async fn acquire(&self) -> Result<RatePermit<'_>, AppError> {
+        self.bucket.lock().unwrap().check_blocked(Instant::now())?;
+        let permit = self.permits.acquire().await.expect("Rate limiter semaphore closed");
+
+        let mut throttled = false;
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                bucket.refill(now, self.tokens_per_sec(), self.max_concurrency as f64);
+                // Deferred while this request waited
+                bucket.check_blocked(now)?;
+
+                match bucket.wait_time(self.tokens_per_sec()) {
+                    Some(wait) => {
+                        if !throttled {
+                            bucket.throttled_requests += 1;
+                        }
+                        wait
+                    }
+                    None => {
+                        bucket.tokens -= 1.0;
+                        bucket.recent.push_back(now);
+                        bucket.total_requests += 1;
+                        return Ok(RatePermit { _permit: permit });
+                    }
+                }
+            };
+
+            throttled = true;
+            log::debug!("Upstream request budget exhausted, waiting {:?}", wait);
+            actix_rt::time::sleep(wait).await;
+        }
+    }
+
+    /// Refuses every request for `retry_after`, as asked by the site
+    pub fn defer(&self, retry_after: Duration) {
+        let retry_after = retry_after.min(MAX_RETRY_AFTER);
+        let mut bucket = self.bucket.lock().unwrap();
+        let blocked_until = Instant::now() + retry_after;
+
+        log::warn!("Upstream asked to retry after {:?}, holding back requests", retry_after);
+        bucket.blocked_until = bucket.blocked_until.max(Some(blocked_until));
+        bucket.tokens = 0.0;
+        bucket.retry_after_deferrals += 1;
+    }
+
+    pub fn status(&self) -> RateLimitStatus {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        bucket.refill(now, self.tokens_per_sec(), self.max_concurrency as f64);
+
+        let blocked_until = bucket.blocked_for(now)
+            .and_then(|remaining| chrono::Duration::from_std(remaining).ok())
+            .map(|remaining| Utc::now() + remaining);
+
+        RateLimitStatus {
+            requests_per_minute: self.requests_per_minute,
+            max_concurrency: self.max_concurrency,
+            in_flight: self.max_concurrency - self.permits.available_permits(),
+            available_tokens: (bucket.tokens * 100.0).round() / 100.0,
+            requests_last_minute: bucket.recent.len(),
+            budget_used: bucket.recent.len() as f64 / self.requests_per_minute as f64,
+            total_requests: bucket.total_requests,
+            throttled_requests: bucket.throttled_requests,
+            retry_after_deferrals: bucket.retry_after_deferrals,
+            blocked_requests: bucket.blocked_requests,
+            blocked_until,
+        }
+    }
+}
+
+/// Reads a `Retry-After` value, given either in seconds or as an HTTP date
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    DateTime::parse_from_rfc2822(value).ok()
+        .map(|retry_at| (retry_at.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(requests_per_minute: u32, max_concurrency: usize) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            requests_per_minute,
+            max_concurrency,
+        })
+    }
+
+    #[actix_rt::test]
+    async fn acquire_takes_a_token_per_request() {
+        let limiter = limiter(600, 2);
+
+        let first = limiter.acquire().await.unwrap();
+        let second = limiter.acquire().await.unwrap();
+        let status = limiter.status();
+        assert_eq!(status.in_flight, 2);
+        assert_eq!(status.total_requests, 2);
+        assert_eq!(status.requests_last_minute, 2);
+
+        drop(first);
+        drop(second);
+        assert_eq!(limiter.status().in_flight, 0);
+    }
+
+    #[actix_rt::test]
+    async fn acquire_fails_fast_while_deferred() {
+        let limiter = limiter(600, 2);
+        limiter.defer(Duration::from_secs(600));
+
+        let started_at = Instant::now();
+        let result = limiter.acquire().await;
+        assert!(matches!(result, Err(AppError::UpstreamRateLimited(_))));
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+
+        let status = limiter.status();
+        assert_eq!(status.in_flight, 0);
+        assert_eq!(status.total_requests, 0);
+        assert_eq!(status.retry_after_deferrals, 1);
+        assert_eq!(status.blocked_requests, 1);
+        assert!(status.blocked_until.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn acquire_resumes_after_the_delay() {
+        let limiter = limiter(6000, 1);
+        limiter.defer(Duration::from_millis(20));
+        assert!(limiter.acquire().await.is_err());
+
+        actix_rt::time::sleep(Duration::from_millis(30)).await;
+        assert!(limiter.acquire().await.is_ok());
+        assert!(limiter.status().blocked_until.is_none());
+    }
+
+    #[test]
+    fn defer_is_capped() {
+        let limiter = limiter(600, 1);
+        limiter.defer(Duration::from_secs(24 * 60 * 60));
+
+        let blocked_until = limiter.status().blocked_until.unwrap();
+        assert!(blocked_until <= Utc::now() + chrono::Duration::from_std(MAX_RETRY_AFTER).unwrap());
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
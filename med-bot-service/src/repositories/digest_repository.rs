@@ -0,0 +1,117 @@
+use crate::models::documents::DigestItem;
+use crate::repositories::ensure_ttl_index;
+use futures::TryStreamExt;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, DateTime, Document};
+use mongodb::{
+    error::Error,
+    results::{InsertManyResult, UpdateResult},
+    Collection,
+};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct MongoDigestRepository {
+    col: Collection<DigestItem>,
+}
+
+impl MongoDigestRepository {
+    pub fn builder(collection: Collection<DigestItem>) -> MongoDigestRepositoryBuilder {
+        MongoDigestRepositoryBuilder::new(collection)
+    }
+
+    pub async fn insert_items(&self, items: &[DigestItem]) -> Result<InsertManyResult, Error> {
+        self.col
+            .insert_many(items)
+            .await
+    }
+
+    /// Expires items `retention` after they were sent, pending items have no `sent_at` and are kept
+    pub async fn ensure_indexes(&self, retention: Duration) -> Result<(), Error> {
+        ensure_ttl_index(&self.col, "sent_at", retention).await
+    }
+
+    /// Claims for the digest `claim_id` every unsent item that is not claimed, or whose claim dates
+    /// from before `stale_before`: that digest died before marking it sent or releasing it
+    pub async fn claim_pending(&self, claim_id: ObjectId, sending_at: DateTime, stale_before: DateTime) -> Result<UpdateResult, Error> {
+        let filter = claim_filter(stale_before);
+        let update = doc! {"$set": {"sending_at": sending_at, "claim_id": claim_id}};
+        self.col
+            .update_many(filter, update)
+            .await
+    }
+
+    /// Items claimed by `claim_id`, oldest first
+    pub async fn find_claimed(&self, claim_id: ObjectId) -> Result<Vec<DigestItem>, Error> {
+        let filter = doc! {"claim_id": claim_id};
+        self.col
+            .find(filter)
+            .sort(doc! {"found_at": 1})
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// Hands the items back to the next digest when this one could not be sent
+    pub async fn release_claim(&self, claim_id: ObjectId) -> Result<UpdateResult, Error> {
+        let filter = doc! {"claim_id": claim_id, "sent_at": null};
+        let update = doc! {"$unset": {"sending_at": "", "claim_id": ""}};
+        self.col
+            .update_many(filter, update)
+            .await
+    }
+
+    pub async fn mark_sent(&self, claim_id: ObjectId, sent_at: DateTime) -> Result<UpdateResult, Error> {
+        let filter = doc! {"claim_id": claim_id};
+        let update = doc! {"$set": {"sent_at": sent_at}};
+        self.col
+            .update_many(filter, update)
+            .await
+    }
+}
+
+fn claim_filter(stale_before: DateTime) -> Document {
+    doc! {
+        "sent_at": null,
+        "$or": [
+            {"sending_at": null},
+            {"sending_at": {"$lt": stale_before}},
+        ],
+    }
+}
+
+pub struct MongoDigestRepositoryBuilder {
+    col: Option<Collection<DigestItem>>,
+}
+
+impl MongoDigestRepositoryBuilder {
+    pub fn new(collection: Collection<DigestItem>) -> MongoDigestRepositoryBuilder {
+        MongoDigestRepositoryBuilder {
+            col: Some(collection),
+        }
+    }
+
+    pub fn build(self) -> MongoDigestRepository {
+        MongoDigestRepository {
+            col: self.col.expect("Digest item collection not initialized"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claims_unclaimed_and_stale_items() {
+        let stale_before = DateTime::from_millis(1_727_827_200_000);
+
+        assert_eq!(claim_filter(stale_before), doc! {
+            "sent_at": null,
+            "$or": [
+                {"sending_at": null},
+                {"sending_at": {"$lt": DateTime::from_millis(1_727_827_200_000)}},
+            ],
+        });
+    }
+}
@@ -1,13 +1,20 @@
 extern crate dotenv;
 
 use crate::models::documents::Doctor;
-use mongodb::bson::doc;
+use crate::repositories::is_index_key_conflict;
+use futures::TryStreamExt;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, Document};
+use mongodb::options::{IndexOptions, ReturnDocument};
 use mongodb::{
     error::Error,
-    Collection,
+    results::{DeleteResult, InsertOneResult, UpdateResult},
+    Collection, IndexModel,
 };
 
-#[derive(Debug)]
+const WINDOW_INDEX: &str = "doctor_window";
+
+#[derive(Debug, Clone)]
 pub struct MongoDoctorRepository {
     col: Collection<Doctor>,
 }
@@ -17,48 +24,94 @@ impl MongoDoctorRepository {
         MongoDoctorRepositoryBuilder::new(collection)
     }
 
-    pub async fn get_doctor_by_doctor_ref_id(&self, doctor_ref_id: String) -> Result<Option<Doctor>, Error> {
-        let filter = doc! {"doctor_ref_id": doctor_ref_id, "active": true};
-        match self.col
-            .find_one(filter)
-            .await {
-            Ok(Some(doctor_detail)) => Ok(Some(doctor_detail)),
-            Ok(None) => Ok(None),
-            Err(e) => Err(e),
+    /// A doctor is tracked at most once per subject, service, hospital and date window. An older
+    /// `doctor_window` index without the subject, service and hospital is replaced.
+    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+        let window_index = IndexModel::builder()
+            .keys(doc! {
+                "doctor_ref_id": 1,
+                "subject_name": 1,
+                "service_name": 1,
+                "hospital_id": 1,
+                "target_date": 1,
+                "date_from": 1,
+                "date_to": 1,
+                "window_days": 1,
+            })
+            .options(IndexOptions::builder().unique(true).name(WINDOW_INDEX.to_string()).build())
+            .build();
+
+        match self.col.create_index(window_index.clone()).await {
+            Err(e) if is_index_key_conflict(&e) => {
+                log::info!("Replacing the {} index of {}", WINDOW_INDEX, self.col.name());
+                self.col.drop_index(WINDOW_INDEX).await?;
+                self.col.create_index(window_index).await?;
+            }
+            result => {
+                result?;
+            }
         }
+        Ok(())
     }
 
-    pub async fn get_doctor_by_doctor_name(&self, doctor_name: String) -> Result<Option<Doctor>, Error> {
+    pub async fn get_target_doctors(&self) -> Result<Vec<Doctor>, Error> {
         let filter = doc! {
-            "doctor_name": {
-                "$regex": doctor_name,
-                "$options": "i"  // Case-insensitive search
-            },
+            "current_target": true,
             "active": true
         };
 
-        match self.col
-            .find_one(filter)
-            .await {
-            Ok(Some(doctor_detail)) => Ok(Some(doctor_detail)),
-            Ok(None) => Ok(None),
-            Err(e) => Err(e),
-        }
+        self.col
+            .find(filter)
+            .await?
+            .try_collect()
+            .await
     }
 
-    pub async fn get_target_doctor(&self) -> Result<Option<Doctor>, Error> {
-        let filter = doc! {
-            "current_target": true,
-            "active": true
-        };
+    pub async fn get_doctor_by_id(&self, id: ObjectId) -> Result<Option<Doctor>, Error> {
+        self.col
+            .find_one(doc! {"_id": id})
+            .await
+    }
 
-        match self.col
-            .find_one(filter)
-            .await {
-            Ok(Some(doctor_detail)) => Ok(Some(doctor_detail)),
-            Ok(None) => Ok(None),
-            Err(e) => Err(e),
-        }
+    pub async fn find_doctors(&self, filter: Document) -> Result<Vec<Doctor>, Error> {
+        self.col
+            .find(filter)
+            .sort(doc! {"doctor_name": 1})
+            .await?
+            .try_collect()
+            .await
+    }
+
+    pub async fn insert_doctor(&self, doctor: &Doctor) -> Result<InsertOneResult, Error> {
+        self.col
+            .insert_one(doctor)
+            .await
+    }
+
+    pub async fn replace_doctor(&self, id: ObjectId, doctor: &Doctor) -> Result<UpdateResult, Error> {
+        self.col
+            .replace_one(doc! {"_id": id}, doctor)
+            .await
+    }
+
+    pub async fn update_doctor(&self, id: ObjectId, update: Document) -> Result<UpdateResult, Error> {
+        self.col
+            .update_one(doc! {"_id": id}, doc! {"$set": update})
+            .await
+    }
+
+    /// Applies a `$set`/`$unset` update in one write and returns the updated document
+    pub async fn patch_doctor(&self, id: ObjectId, update: Document) -> Result<Option<Doctor>, Error> {
+        self.col
+            .find_one_and_update(doc! {"_id": id}, update)
+            .return_document(ReturnDocument::After)
+            .await
+    }
+
+    pub async fn delete_doctor(&self, id: ObjectId) -> Result<DeleteResult, Error> {
+        self.col
+            .delete_one(doc! {"_id": id})
+            .await
     }
 }
 
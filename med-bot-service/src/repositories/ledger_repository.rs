@@ -0,0 +1,111 @@
+use crate::models::documents::NotificationLedgerEntry;
+use crate::repositories::ensure_ttl_index;
+use futures::TryStreamExt;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, to_bson, DateTime, Document};
+use mongodb::options::IndexOptions;
+use mongodb::{
+    error::Error,
+    results::UpdateResult,
+    Collection, IndexModel,
+};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct MongoLedgerRepository {
+    col: Collection<NotificationLedgerEntry>,
+}
+
+impl MongoLedgerRepository {
+    pub fn builder(collection: Collection<NotificationLedgerEntry>) -> MongoLedgerRepositoryBuilder {
+        MongoLedgerRepositoryBuilder::new(collection)
+    }
+
+    /// Expires entries `retention` after they were last notified, keeps one entry per slot and
+    /// channel, and speeds up the cooldown lookup
+    pub async fn ensure_indexes(&self, retention: Duration) -> Result<(), Error> {
+        ensure_ttl_index(&self.col, "last_notified_at", retention).await?;
+
+        let slot_index = IndexModel::builder()
+            .keys(doc! {"target_id": 1, "appointment_date": 1, "time_id": 1, "channel": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        let cooldown_index = IndexModel::builder()
+            .keys(doc! {"target_id": 1, "channel": 1, "last_notified_at": 1})
+            .build();
+        self.col
+            .create_indexes([slot_index, cooldown_index])
+            .await?;
+        Ok(())
+    }
+
+    pub async fn find_notified_since(&self, target_id: ObjectId, channel: &str, since: DateTime) -> Result<Vec<NotificationLedgerEntry>, Error> {
+        let filter = doc! {
+            "target_id": target_id,
+            "channel": channel,
+            "last_notified_at": {"$gte": since},
+        };
+        self.col
+            .find(filter)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// One entry per target, date, `time_id` and channel, counting how often it was notified
+    pub async fn upsert_entry(&self, entry: &NotificationLedgerEntry) -> Result<UpdateResult, Error> {
+        let filter = doc! {
+            "target_id": entry.target_id,
+            "appointment_date": &entry.appointment_date,
+            "time_id": &entry.time_id,
+            "channel": &entry.channel,
+        };
+        let update = doc! {
+            "$set": {
+                "doctor_ref_id": &entry.doctor_ref_id,
+                "doctor_name": &entry.doctor_name,
+                "recipient": &entry.recipient,
+                "change_type": to_bson(&entry.change_type)?,
+                "shift_code": &entry.shift_code,
+                "start_time": &entry.start_time,
+                "available_slot": entry.available_slot,
+                "last_notified_at": entry.last_notified_at,
+            },
+            "$inc": {"notify_count": 1},
+        };
+        self.col
+            .update_one(filter, update)
+            .upsert(true)
+            .await
+    }
+
+    /// Most recently notified first
+    pub async fn find_entries(&self, filter: Document, limit: i64) -> Result<Vec<NotificationLedgerEntry>, Error> {
+        self.col
+            .find(filter)
+            .sort(doc! {"last_notified_at": -1})
+            .limit(limit)
+            .await?
+            .try_collect()
+            .await
+    }
+}
+
+
+pub struct MongoLedgerRepositoryBuilder {
+    col: Option<Collection<NotificationLedgerEntry>>,
+}
+
+impl MongoLedgerRepositoryBuilder {
+    pub fn new(collection: Collection<NotificationLedgerEntry>) -> MongoLedgerRepositoryBuilder {
+        MongoLedgerRepositoryBuilder {
+            col: Some(collection),
+        }
+    }
+
+    pub fn build(self) -> MongoLedgerRepository {
+        MongoLedgerRepository {
+            col: self.col.expect("Notification ledger collection not initialized"),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::error::AppError;
+use crate::models::documents::RunLock;
+use crate::repositories::is_duplicate_key;
+use async_trait::async_trait;
+use mongodb::bson::{doc, DateTime};
+use mongodb::options::ReturnDocument;
+use mongodb::Collection;
+use std::fmt::Debug;
+
+/// Where run lock leases are kept, shared by every replica
+#[async_trait(?Send)]
+pub trait LockStore: Debug + Send + Sync {
+    /// Takes the lock when it is free, expired or already ours, and returns `None` when another owner holds it
+    async fn try_acquire(&self, name: &str, owner: &str, expires_at: DateTime) -> Result<Option<RunLock>, AppError>;
+
+    async fn get_lock(&self, name: &str) -> Result<Option<RunLock>, AppError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct MongoLockRepository {
+    col: Collection<RunLock>,
+}
+
+impl MongoLockRepository {
+    pub fn builder(collection: Collection<RunLock>) -> MongoLockRepositoryBuilder {
+        MongoLockRepositoryBuilder::new(collection)
+    }
+}
+
+#[async_trait(?Send)]
+impl LockStore for MongoLockRepository {
+    /// When another owner holds the lock the upsert collides with the existing `_id`
+    async fn try_acquire(&self, name: &str, owner: &str, expires_at: DateTime) -> Result<Option<RunLock>, AppError> {
+        let now = DateTime::now();
+        let filter = doc! {
+            "_id": name,
+            "$or": [
+                {"owner": owner},
+                {"expires_at": {"$lte": now}},
+            ],
+        };
+        let update = doc! {
+            "$set": {
+                "owner": owner,
+                "acquired_at": now,
+                "expires_at": expires_at,
+            }
+        };
+
+        let result = self.col
+            .find_one_and_update(filter, update)
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .await;
+
+        match result {
+            Err(e) if is_duplicate_key(&e) => Ok(None),
+            result => Ok(result?),
+        }
+    }
+
+    async fn get_lock(&self, name: &str) -> Result<Option<RunLock>, AppError> {
+        let filter = doc! {"_id": name};
+        Ok(self.col
+            .find_one(filter)
+            .await?)
+    }
+}
+
+
+pub struct MongoLockRepositoryBuilder {
+    col: Option<Collection<RunLock>>,
+}
+
+impl MongoLockRepositoryBuilder {
+    pub fn new(collection: Collection<RunLock>) -> MongoLockRepositoryBuilder {
+        MongoLockRepositoryBuilder {
+            col: Some(collection),
+        }
+    }
+
+    pub fn build(self) -> MongoLockRepository {
+        MongoLockRepository {
+            col: self.col.expect("Run lock collection not initialized"),
+        }
+    }
+}
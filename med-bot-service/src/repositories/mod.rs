@@ -1,2 +1,63 @@
-pub mod user_repository;
-pub mod doctor_repository;
\ No newline at end of file
+pub mod doctor_repository;
+pub mod snapshot_repository;
+pub mod lock_repository;
+pub mod run_repository;
+pub mod observation_repository;
+pub mod ledger_repository;
+pub mod digest_repository;
+
+use mongodb::bson::doc;
+use mongodb::error::{Error, ErrorKind, WriteFailure};
+use mongodb::options::IndexOptions;
+use mongodb::{Collection, IndexModel};
+use std::time::Duration;
+
+const DUPLICATE_KEY_CODE: i32 = 11000;
+const INDEX_OPTIONS_CONFLICT_CODE: i32 = 85;
+const INDEX_KEY_SPECS_CONFLICT_CODE: i32 = 86;
+
+/// Whether a write collided with a unique index
+pub fn is_duplicate_key(error: &Error) -> bool {
+    match error.kind.as_ref() {
+        ErrorKind::Command(command_error) => command_error.code == DUPLICATE_KEY_CODE,
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => write_error.code == DUPLICATE_KEY_CODE,
+        _ => false,
+    }
+}
+
+/// Expires documents `retention` after their `field` date. When the index already exists with
+/// another retention, its `expireAfterSeconds` is updated in place with `collMod`.
+pub async fn ensure_ttl_index<T: Send + Sync>(col: &Collection<T>, field: &str, retention: Duration) -> Result<(), Error> {
+    let ttl_index = IndexModel::builder()
+        .keys(doc! {field: 1})
+        .options(IndexOptions::builder().expire_after(retention).build())
+        .build();
+
+    match col.create_index(ttl_index).await {
+        Ok(_) => Ok(()),
+        Err(e) if is_index_options_conflict(&e) => {
+            log::info!("Changing the {}.{} retention to {}s", col.name(), field, retention.as_secs());
+            col.client()
+                .database(&col.namespace().db)
+                .run_command(doc! {
+                    "collMod": col.name(),
+                    "index": {
+                        "keyPattern": {field: 1},
+                        "expireAfterSeconds": retention.as_secs() as i64,
+                    },
+                })
+                .await?;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn is_index_options_conflict(error: &Error) -> bool {
+    matches!(error.kind.as_ref(), ErrorKind::Command(command_error) if command_error.code == INDEX_OPTIONS_CONFLICT_CODE)
+}
+
+/// Whether an index with the same name already exists with other keys
+pub fn is_index_key_conflict(error: &Error) -> bool {
+    matches!(error.kind.as_ref(), ErrorKind::Command(command_error) if command_error.code == INDEX_KEY_SPECS_CONFLICT_CODE)
+}
\ No newline at end of file
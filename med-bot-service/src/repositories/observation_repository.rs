@@ -0,0 +1,67 @@
+use crate::models::documents::SlotObservation;
+use crate::repositories::ensure_ttl_index;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::{
+    error::Error,
+    Collection, IndexModel,
+};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct MongoObservationRepository {
+    col: Collection<SlotObservation>,
+}
+
+impl MongoObservationRepository {
+    pub fn builder(collection: Collection<SlotObservation>) -> MongoObservationRepositoryBuilder {
+        MongoObservationRepositoryBuilder::new(collection)
+    }
+
+    /// Expires observations `retention` after `observed_at`, and speeds up per target queries
+    pub async fn ensure_indexes(&self, retention: Duration) -> Result<(), Error> {
+        ensure_ttl_index(&self.col, "observed_at", retention).await?;
+
+        let target_index = IndexModel::builder()
+            .keys(doc! {"target_id": 1, "observed_at": -1})
+            .build();
+        self.col
+            .create_index(target_index)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn insert_observations(&self, observations: &[SlotObservation]) -> Result<(), Error> {
+        self.col
+            .insert_many(observations)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn aggregate(&self, pipeline: Vec<Document>) -> Result<Vec<Document>, Error> {
+        self.col
+            .aggregate(pipeline)
+            .await?
+            .try_collect()
+            .await
+    }
+}
+
+
+pub struct MongoObservationRepositoryBuilder {
+    col: Option<Collection<SlotObservation>>,
+}
+
+impl MongoObservationRepositoryBuilder {
+    pub fn new(collection: Collection<SlotObservation>) -> MongoObservationRepositoryBuilder {
+        MongoObservationRepositoryBuilder {
+            col: Some(collection),
+        }
+    }
+
+    pub fn build(self) -> MongoObservationRepository {
+        MongoObservationRepository {
+            col: self.col.expect("Slot observation collection not initialized"),
+        }
+    }
+}
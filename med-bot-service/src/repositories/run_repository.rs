@@ -0,0 +1,78 @@
+use crate::models::documents::RunRecord;
+use crate::repositories::ensure_ttl_index;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::{
+    error::Error,
+    results::InsertOneResult,
+    Collection, IndexModel,
+};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct MongoRunRepository {
+    col: Collection<RunRecord>,
+}
+
+impl MongoRunRepository {
+    pub fn builder(collection: Collection<RunRecord>) -> MongoRunRepositoryBuilder {
+        MongoRunRepositoryBuilder::new(collection)
+    }
+
+    /// Expires runs `retention` after `started_at`, which also serves the newest first listing,
+    /// and speeds up the per target listing
+    pub async fn ensure_indexes(&self, retention: Duration) -> Result<(), Error> {
+        ensure_ttl_index(&self.col, "started_at", retention).await?;
+
+        let target_index = IndexModel::builder()
+            .keys(doc! {"targets.target_id": 1, "started_at": -1})
+            .build();
+        self.col
+            .create_index(target_index)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn insert_run(&self, run: &RunRecord) -> Result<InsertOneResult, Error> {
+        self.col
+            .insert_one(run)
+            .await
+    }
+
+    /// Newest runs first
+    pub async fn find_runs(&self, filter: Document, skip: u64, limit: i64) -> Result<Vec<RunRecord>, Error> {
+        self.col
+            .find(filter)
+            .sort(doc! {"started_at": -1})
+            .skip(skip)
+            .limit(limit)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    pub async fn count_runs(&self, filter: Document) -> Result<u64, Error> {
+        self.col
+            .count_documents(filter)
+            .await
+    }
+}
+
+
+pub struct MongoRunRepositoryBuilder {
+    col: Option<Collection<RunRecord>>,
+}
+
+impl MongoRunRepositoryBuilder {
+    pub fn new(collection: Collection<RunRecord>) -> MongoRunRepositoryBuilder {
+        MongoRunRepositoryBuilder {
+            col: Some(collection),
+        }
+    }
+
+    pub fn build(self) -> MongoRunRepository {
+        MongoRunRepository {
+            col: self.col.expect("Run record collection not initialized"),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::models::documents::SlotSnapshot;
+use mongodb::bson::doc;
+use mongodb::bson::oid::ObjectId;
+use mongodb::options::IndexOptions;
+use mongodb::{
+    error::Error,
+    results::UpdateResult,
+    Collection, IndexModel,
+};
+
+#[derive(Debug, Clone)]
+pub struct MongoSnapshotRepository {
+    col: Collection<SlotSnapshot>,
+}
+
+impl MongoSnapshotRepository {
+    pub fn builder(collection: Collection<SlotSnapshot>) -> MongoSnapshotRepositoryBuilder {
+        MongoSnapshotRepositoryBuilder::new(collection)
+    }
+
+    /// One snapshot is kept per target, so the lookup by `target_id` is unique
+    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+        let target_index = IndexModel::builder()
+            .keys(doc! {"target_id": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+
+        self.col
+            .create_index(target_index)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_latest_snapshot(&self, target_id: ObjectId) -> Result<Option<SlotSnapshot>, Error> {
+        let filter = doc! {"target_id": target_id};
+        self.col
+            .find_one(filter)
+            .sort(doc! {"taken_at": -1})
+            .await
+    }
+
+    /// Replaces the previous snapshot of the target
+    pub async fn upsert_snapshot(&self, snapshot: SlotSnapshot) -> Result<UpdateResult, Error> {
+        let filter = doc! {"target_id": snapshot.target_id};
+        self.col
+            .replace_one(filter, snapshot)
+            .upsert(true)
+            .await
+    }
+}
+
+
+pub struct MongoSnapshotRepositoryBuilder {
+    col: Option<Collection<SlotSnapshot>>,
+}
+
+impl MongoSnapshotRepositoryBuilder {
+    pub fn new(collection: Collection<SlotSnapshot>) -> MongoSnapshotRepositoryBuilder {
+        MongoSnapshotRepositoryBuilder {
+            col: Some(collection),
+        }
+    }
+
+    pub fn build(self) -> MongoSnapshotRepository {
+        MongoSnapshotRepository {
+            col: self.col.expect("Slot snapshot collection not initialized"),
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::config::retry_config::RetryConfig;
+use crate::error::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls are rejected until the cooldown ends
+    Open,
+    /// A single trial call decides whether the circuit closes again
+    HalfOpen,
+}
+
+/// Circuit breaker state reported on `/healthz`
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitStatus {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub opened_at: Option<DateTime<Utc>>,
+    /// When an open circuit lets the next trial call through
+    pub retry_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<DateTime<Utc>>,
+    retry_at: Option<DateTime<Utc>>,
+    /// Set while the half-open trial call is running, so concurrent calls keep being rejected
+    trial_in_flight: bool,
+}
+
+/// Stops calling the booking site after `threshold` consecutive failed requests
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    circuit: Mutex<Circuit>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            circuit: Mutex::new(Circuit {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                retry_at: None,
+                trial_in_flight: false,
+            }),
+        }
+    }
+
+    pub fn status(&self) -> CircuitStatus {
+        let circuit = self.circuit.lock().unwrap();
+        CircuitStatus {
+            state: circuit.state,
+            consecutive_failures: circuit.consecutive_failures,
+            opened_at: circuit.opened_at,
+            retry_at: circuit.retry_at,
+        }
+    }
+
+    /// Rejects the call while the circuit is open, or half-open with the trial call still running
+    fn allow(&self, endpoint: &str) -> Result<(), AppError> {
+        let mut circuit = self.circuit.lock().unwrap();
+        let now = Utc::now();
+
+        if circuit.state == CircuitState::Open && circuit.retry_at.is_none_or(|retry_at| retry_at <= now) {
+            log::info!("Circuit half-open, letting a trial {} request through", endpoint);
+            circuit.state = CircuitState::HalfOpen;
+            circuit.trial_in_flight = false;
+        }
+
+        match circuit.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::HalfOpen if !circuit.trial_in_flight => {
+                circuit.trial_in_flight = true;
+                Ok(())
+            }
+            _ => Err(AppError::UpstreamUnavailable(format!(
+                "circuit open after {} consecutive failures, {} not called", circuit.consecutive_failures, endpoint
+            ))),
+        }
+    }
+
+    /// Lets another call run the half-open trial when this one never reached the site
+    fn release_trial(&self) {
+        let mut circuit = self.circuit.lock().unwrap();
+        if circuit.state == CircuitState::HalfOpen {
+            circuit.trial_in_flight = false;
+        }
+    }
+
+    fn record_success(&self) {
+        let mut circuit = self.circuit.lock().unwrap();
+        if circuit.state != CircuitState::Closed {
+            log::info!("Circuit closed, upstream answered again");
+        }
+        circuit.state = CircuitState::Closed;
+        circuit.consecutive_failures = 0;
+        circuit.opened_at = None;
+        circuit.retry_at = None;
+        circuit.trial_in_flight = false;
+    }
+
+    fn record_failure(&self) {
+        let mut circuit = self.circuit.lock().unwrap();
+        circuit.consecutive_failures += 1;
+
+        let should_open = match circuit.state {
+            CircuitState::Closed => circuit.consecutive_failures >= self.threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if should_open {
+            let now = Utc::now();
+            log::warn!("Circuit open after {} consecutive upstream failures", circuit.consecutive_failures);
+            circuit.state = CircuitState::Open;
+            circuit.opened_at = Some(now);
+            circuit.retry_at = chrono::Duration::from_std(self.cooldown).ok()
+                .and_then(|cooldown| now.checked_add_signed(cooldown));
+            circuit.trial_in_flight = false;
+        }
+    }
+}
+
+/// Retries failed booking site calls with exponential backoff, behind a shared circuit breaker
+#[derive(Debug)]
+pub struct UpstreamGuard {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    breaker: CircuitBreaker,
+}
+
+impl UpstreamGuard {
+    pub fn new(retry_config: &RetryConfig) -> UpstreamGuard {
+        UpstreamGuard {
+            max_attempts: retry_config.max_attempts,
+            base_delay: retry_config.base_delay,
+            max_delay: retry_config.max_delay,
+            breaker: CircuitBreaker::new(retry_config.breaker_threshold, retry_config.breaker_cooldown),
+        }
+    }
+
+    pub fn circuit_status(&self) -> CircuitStatus {
+        self.breaker.status()
+    }
+
+    /// Runs `call` until it succeeds, fails with an error not worth retrying, or runs out of attempts
+    pub async fn call<T, F, Fut>(&self, endpoint: &str, mut call: F) -> Result<T, AppError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, AppError>>,
+    {
+        let mut attempt = 1;
+        let mut last_error = None;
+        loop {
+            if let Err(e) = self.breaker.allow(endpoint) {
+                // Opened by our own failed attempts, the upstream error says more
+                return Err(last_error.unwrap_or(e));
+            }
+
+            let error = match call().await {
+                Ok(response) => {
+                    self.breaker.record_success();
+                    return Ok(response);
+                }
+                // Held back by the rate limiter before reaching the site, so it says nothing about
+                // upstream health; a half-open trial is handed to the next call
+                Err(e @ AppError::UpstreamRateLimited(_)) => {
+                    self.breaker.release_trial();
+                    return Err(e);
+                }
+                Err(e) if is_retryable(&e) => e,
+                Err(e) => {
+                    // The site answered, only not the way we wanted
+                    self.breaker.record_success();
+                    return Err(e);
+                }
+            };
+
+            self.breaker.record_failure();
+            if attempt >= self.max_attempts {
+                return Err(error);
+            }
+
+            let delay = self.backoff(attempt);
+            log::warn!("Upstream {} attempt {}/{} failed: {}. Retrying in {:?}", endpoint, attempt, self.max_attempts, error, delay);
+            last_error = Some(error);
+            actix_rt::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Exponential delay for the retry after `attempt`, with the upper half randomised so
+    /// concurrent checks don't retry in lockstep
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+        let half = delay / 2;
+        half + Duration::from_millis(fastrand::u64(0..=half.as_millis() as u64))
+    }
+}
+
+/// Timeouts, connection failures, rate limiting and server errors are transient
+fn is_retryable(error: &AppError) -> bool {
+    match error {
+        AppError::UpstreamHttp(e) => e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+        AppError::UpstreamStatus { status, .. } => *status == 429 || *status >= 500,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn guard(max_attempts: u32, breaker_threshold: u32) -> UpstreamGuard {
+        UpstreamGuard::new(&RetryConfig {
+            max_attempts,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            breaker_threshold,
+            breaker_cooldown: Duration::from_secs(60),
+        })
+    }
+
+    fn server_error() -> AppError {
+        AppError::UpstreamStatus { endpoint: "search".to_string(), status: 503 }
+    }
+
+    #[test]
+    fn circuit_opens_after_threshold_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.allow("search").unwrap();
+        breaker.record_failure();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+
+        breaker.allow("search").unwrap();
+        breaker.record_failure();
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Open);
+        assert_eq!(status.consecutive_failures, 2);
+        assert!(status.retry_at.is_some());
+
+        // Still cooling down
+        assert!(matches!(breaker.allow("search"), Err(AppError::UpstreamUnavailable(_))));
+    }
+
+    #[test]
+    fn success_resets_the_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Closed);
+        assert_eq!(status.consecutive_failures, 1);
+    }
+
+    #[test]
+    fn half_open_lets_a_single_trial_through() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record_failure();
+        assert_eq!(breaker.status().state, CircuitState::Open);
+
+        breaker.allow("search").unwrap();
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+        // Concurrent calls wait for the trial outcome
+        assert!(matches!(breaker.allow("search"), Err(AppError::UpstreamUnavailable(_))));
+    }
+
+    #[test]
+    fn successful_trial_closes_the_circuit() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record_failure();
+
+        breaker.allow("search").unwrap();
+        breaker.record_success();
+
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Closed);
+        assert_eq!(status.consecutive_failures, 0);
+        assert!(status.opened_at.is_none());
+        assert!(status.retry_at.is_none());
+        breaker.allow("search").unwrap();
+        breaker.allow("search").unwrap();
+    }
+
+    #[test]
+    fn failed_trial_opens_the_circuit_again() {
+        let breaker = CircuitBreaker::new(3, Duration::ZERO);
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+
+        breaker.allow("search").unwrap();
+        breaker.record_failure();
+
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Open);
+        assert_eq!(status.consecutive_failures, 4);
+
+        // The cooldown is over at once, so the next call is a new trial
+        breaker.allow("search").unwrap();
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_delay() {
+        let guard = UpstreamGuard::new(&RetryConfig {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::ZERO,
+        });
+
+        for (attempt, full_delay) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (20, 1000)] {
+            for _ in 0..20 {
+                let delay = guard.backoff(attempt).as_millis();
+                assert!(
+                    (full_delay / 2..=full_delay).contains(&delay),
+                    "attempt {} waited {}ms, expected {}..={}ms", attempt, delay, full_delay / 2, full_delay
+                );
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn call_retries_transient_errors() {
+        let guard = guard(3, 5);
+        let calls = Cell::new(0);
+
+        let result = guard.call("search", || {
+            calls.set(calls.get() + 1);
+            let attempt = calls.get();
+            async move { if attempt < 3 { Err(server_error()) } else { Ok(attempt) } }
+        }).await;
+
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(guard.circuit_status().state, CircuitState::Closed);
+        assert_eq!(guard.circuit_status().consecutive_failures, 0);
+    }
+
+    #[actix_rt::test]
+    async fn call_does_not_retry_other_errors() {
+        let guard = guard(3, 5);
+        let calls = Cell::new(0);
+
+        let result: Result<(), AppError> = guard.call("search", || {
+            calls.set(calls.get() + 1);
+            async { Err(AppError::UpstreamStatus { endpoint: "search".to_string(), status: 404 }) }
+        }).await;
+
+        assert!(matches!(result, Err(AppError::UpstreamStatus { status: 404, .. })));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn call_stops_once_the_circuit_opens() {
+        let guard = guard(5, 2);
+        let calls = Cell::new(0);
+
+        let result: Result<(), AppError> = guard.call("search", || {
+            calls.set(calls.get() + 1);
+            async { Err(server_error()) }
+        }).await;
+
+        // The upstream error is kept over the circuit one
+        assert!(matches!(result, Err(AppError::UpstreamStatus { status: 503, .. })));
+        assert_eq!(calls.get(), 2);
+        assert_eq!(guard.circuit_status().state, CircuitState::Open);
+
+        let result: Result<(), AppError> = guard.call("search", || async { Ok(()) }).await;
+        assert!(matches!(result, Err(AppError::UpstreamUnavailable(_))));
+    }
+
+    #[actix_rt::test]
+    async fn rate_limited_trial_lets_the_next_call_through() {
+        let guard = UpstreamGuard::new(&RetryConfig {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            breaker_threshold: 1,
+            breaker_cooldown: Duration::ZERO,
+        });
+        let _: Result<(), AppError> = guard.call("search", || async { Err(server_error()) }).await;
+        assert_eq!(guard.circuit_status().state, CircuitState::Open);
+
+        // The trial is refused by an active Retry-After block
+        let result: Result<(), AppError> = guard.call("search", || async {
+            Err(AppError::UpstreamRateLimited("booking site asked to retry after 60s".to_string()))
+        }).await;
+        assert!(matches!(result, Err(AppError::UpstreamRateLimited(_))));
+        assert_eq!(guard.circuit_status().state, CircuitState::HalfOpen);
+
+        let result = guard.call("search", || async { Ok(()) }).await;
+        assert!(result.is_ok());
+        assert_eq!(guard.circuit_status().state, CircuitState::Closed);
+    }
+}
@@ -1,29 +1,457 @@
-use chrono::{Local, Utc};
+use chrono::{DateTime, Local, Utc};
 use cron::Schedule;
+use futures::FutureExt;
+use mongodb::bson;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::RwLock;
+use std::time::Instant;
 use std::{str::FromStr, time::Duration};
 use actix_web::web::Data;
+use crate::adaptive::{AdaptivePolicy, AdaptiveState};
+use crate::config::scheduler_config::{SchedulerConfig, SchedulerMode};
+use crate::models::doctor_appointment::TargetAnalysis;
+use crate::error::AppError;
+use crate::models::documents::{Doctor, RunContext, RunOutcome, RunTrigger};
 use crate::AppState;
-use crate::services::med_service::MedService;
+
+/// How often the scheduler reloads targets and their schedules from Mongo
+const TARGET_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SchedulerStatus {
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_outcome: Option<RunOutcome>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    pub total_runs: u64,
+    pub total_failures: u64,
+    pub degraded: bool,
+}
+
+/// Outcome of the scheduled runs, shared with the status and health endpoints
+#[derive(Debug)]
+pub struct SchedulerMonitor {
+    failure_threshold: u32,
+    status: RwLock<SchedulerStatus>,
+}
+
+impl SchedulerMonitor {
+    pub fn new(scheduler_config: &SchedulerConfig) -> SchedulerMonitor {
+        SchedulerMonitor {
+            failure_threshold: scheduler_config.failure_threshold,
+            status: RwLock::new(SchedulerStatus::default()),
+        }
+    }
+
+    pub fn status(&self) -> SchedulerStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    pub fn record_run(&self, outcome: RunOutcome, error: Option<String>) {
+        let mut status = self.status.write().unwrap();
+        let now = Utc::now();
+
+        status.last_run_at = Some(now);
+        status.last_outcome = Some(outcome);
+        status.last_error = error;
+        status.total_runs += 1;
+
+        if outcome == RunOutcome::Failed {
+            status.consecutive_failures += 1;
+            status.total_failures += 1;
+        } else {
+            status.consecutive_failures = 0;
+            status.last_success_at = Some(now);
+        }
+        status.degraded = status.consecutive_failures >= self.failure_threshold;
+    }
+}
+
+/// How a target decides when it is checked next
+enum Cadence {
+    /// Fires on each tick of a cron schedule
+    Cron(Box<Schedule>),
+    /// Fires after an interval derived from the target window and its recent slot changes
+    Adaptive(AdaptiveState),
+}
+
+impl Cadence {
+    fn is_same_kind(&self, other: &Cadence) -> bool {
+        match (self, other) {
+            (Cadence::Cron(schedule), Cadence::Cron(other)) => schedule == other,
+            (Cadence::Adaptive(_), Cadence::Adaptive(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A cadence together with its next pending fire time. Times are computed in UTC only,
+/// and cron ticks are advanced past "now" once fired, so each tick fires at most once and
+/// ticks missed while an analysis was running collapse into a single run.
+struct Tick {
+    cadence: Cadence,
+    /// The cron tick or end of the interval being waited for, plus the random jitter.
+    /// `None` while an adaptive check is in flight or when the cron never fires again.
+    fire_at: Option<DateTime<Utc>>,
+}
+
+impl Tick {
+    fn cron(schedule: Schedule, now: DateTime<Utc>, jitter: Duration) -> Tick {
+        let mut tick = Tick { cadence: Cadence::Cron(Box::new(schedule)), fire_at: None };
+        tick.advance(now, jitter);
+        tick
+    }
+
+    /// Adaptive targets are checked right away, their interval is only known after a check
+    fn adaptive(now: DateTime<Utc>) -> Tick {
+        Tick { cadence: Cadence::Adaptive(AdaptiveState::default()), fire_at: Some(now) }
+    }
+
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.fire_at.is_some_and(|fire_at| fire_at <= now)
+    }
+
+    /// Moves a cron tick to the first tick after `now`; adaptive ticks wait for `reschedule`
+    fn advance(&mut self, now: DateTime<Utc>, jitter: Duration) {
+        self.fire_at = match &self.cadence {
+            Cadence::Cron(schedule) => schedule.after(&now).next()
+                .map(|next_tick| next_tick + random_jitter(jitter)),
+            Cadence::Adaptive(_) => None,
+        };
+    }
+
+    fn reschedule(&mut self, now: DateTime<Utc>, interval: Duration, jitter: Duration) {
+        self.fire_at = chrono::Duration::from_std(interval).ok()
+            .and_then(|interval| now.checked_add_signed(interval))
+            .map(|next_tick| next_tick + random_jitter(jitter));
+    }
+}
+
+struct ScheduledTarget {
+    doctor: Doctor,
+    tick: Tick,
+}
+
+fn random_jitter(jitter: Duration) -> chrono::Duration {
+    let millis = fastrand::u64(0..=jitter.as_millis() as u64);
+    chrono::Duration::milliseconds(millis as i64)
+}
 
 pub async fn start_scheduler(app_state: Data<AppState>) {
-    //0 0 0/8 * * *
-    //0 0/5 * * * * template for every 5 minutes
-    let expression = "0 0 0/8 * * *";
-    let schedule = Schedule::from_str(expression).unwrap();
+    let scheduler_config = &app_state.scheduler_config;
+    let jitter = scheduler_config.jitter;
+    let policy = AdaptivePolicy::new(scheduler_config);
+    let mut targets: HashMap<String, ScheduledTarget> = HashMap::new();
+    // Without targets the default tick still runs, so "no target configured" gets reported
+    let mut default_tick = Tick::cron(scheduler_config.default_schedule.clone(), Utc::now(), jitter);
+    let mut refreshed_at: Option<Instant> = None;
 
     loop {
-        let mut upcoming = schedule.upcoming(Utc).take(1);
-        actix_rt::time::sleep(Duration::from_millis(500)).await;
-        let local = &Local::now();
+        // Reload targets so schedules changed through the API apply without a restart
+        if refreshed_at.is_none_or(|refreshed_at| refreshed_at.elapsed() >= TARGET_REFRESH_INTERVAL) {
+            match app_state.service.med_service.get_target_doctors().await {
+                Ok(doctors) => refresh_targets(&mut targets, doctors, scheduler_config),
+                Err(e) => log::error!("Failed to reload target doctors: {}", e),
+            }
+            refreshed_at = Some(Instant::now());
+        }
+
+        let now = Utc::now();
+        let due_targets = targets.values_mut()
+            .filter(|target| target.tick.is_due(now))
+            .map(|target| {
+                target.tick.advance(now, jitter);
+                target.doctor.clone()
+            })
+            .collect::<Vec<Doctor>>();
+        let default_due = targets.is_empty() && default_tick.is_due(now);
+        if default_tick.is_due(now) {
+            default_tick.advance(now, jitter);
+        }
+
+        if !due_targets.is_empty() || default_due {
+            log::info!("Running schedule med bot for {} targets", due_targets.len());
+            // Awaited in place: the next tick is only looked at once this analysis finished
+            let results = run_analysis(&app_state, due_targets).await;
+            reschedule_adaptive(&mut targets, &results, &policy, jitter);
+            continue;
+        }
+
+        let next_fire = targets.values()
+            .map(|target| &target.tick)
+            .chain(targets.is_empty().then_some(&default_tick))
+            .filter_map(|tick| tick.fire_at)
+            .min();
+        let until_refresh = refreshed_at
+            .map(|refreshed_at| TARGET_REFRESH_INTERVAL.saturating_sub(refreshed_at.elapsed()))
+            .unwrap_or_default();
+        let until_fire = next_fire
+            .map(|next_fire| (next_fire - Utc::now()).to_std().unwrap_or_default())
+            .unwrap_or(until_refresh);
+        actix_rt::time::sleep(until_fire.min(until_refresh)).await;
+    }
+}
+
+/// Gives the adaptive targets that were just checked their next fire time
+fn reschedule_adaptive(
+    targets: &mut HashMap<String, ScheduledTarget>,
+    results: &[TargetAnalysis],
+    policy: &AdaptivePolicy,
+    jitter: Duration,
+) {
+    let now = Utc::now();
+    let today = Local::now().date_naive();
+    // Cron targets spend the same request budget
+    let target_count = targets.len();
+
+    for target in targets.values_mut().filter(|target| target.tick.fire_at.is_none()) {
+        let Cadence::Adaptive(state) = &mut target.tick.cadence else {
+            continue;
+        };
+        let changed = results.iter().any(|result| {
+            result.target_id.is_some() && result.target_id == target.doctor.id && !result.changes.is_empty()
+        });
+        state.record_check(changed);
+
+        let interval = policy.interval(&target.doctor, state, today, target_count);
+        log::info!("Next check of {} in {}s", target.doctor.doctor_name, interval.as_secs());
+        target.tick.reschedule(now, interval, jitter);
+    }
+}
+
+/// Keeps the pending tick of targets whose cadence is unchanged, so a reload never
+/// re-fires or skips a tick
+fn refresh_targets(
+    targets: &mut HashMap<String, ScheduledTarget>,
+    doctors: Vec<Doctor>,
+    scheduler_config: &SchedulerConfig,
+) {
+    let now = Utc::now();
+    let mut refreshed = HashMap::with_capacity(doctors.len());
+
+    for doctor in doctors {
+        let key = doctor.id.map(|id| id.to_hex()).unwrap_or_else(|| doctor.doctor_ref_id.clone());
+        // An explicit poll_cron always wins over the adaptive interval
+        let tick = if scheduler_config.mode == SchedulerMode::Adaptive && doctor.poll_cron.is_none() {
+            Tick::adaptive(now)
+        } else {
+            Tick::cron(target_schedule(&doctor, &scheduler_config.default_schedule), now, scheduler_config.jitter)
+        };
+        let tick = match targets.remove(&key) {
+            Some(target) if target.tick.cadence.is_same_kind(&tick.cadence) => target.tick,
+            _ => tick,
+        };
+        refreshed.insert(key, ScheduledTarget { doctor, tick });
+    }
+    *targets = refreshed;
+}
+
+/// The target's own cron expression, or the default one when unset or invalid
+fn target_schedule(doctor: &Doctor, default_schedule: &Schedule) -> Schedule {
+    match &doctor.poll_cron {
+        Some(poll_cron) => Schedule::from_str(poll_cron).unwrap_or_else(|e| {
+            log::error!("Invalid poll_cron {} for {}: {}, using default", poll_cron, doctor.doctor_name, e);
+            default_schedule.clone()
+        }),
+        None => default_schedule.clone(),
+    }
+}
+
+/// Runs one analysis and records its outcome; errors and panics never escape the scheduler loop.
+/// Only the replica holding the run lock analyzes, the others skip the tick.
+async fn run_analysis(app_state: &Data<AppState>, doctors: Vec<Doctor>) -> Vec<TargetAnalysis> {
+    let lock_service = &app_state.service.lock_service;
+    let _run_guard = match lock_service.try_lock_run().await {
+        Ok(Some(run_guard)) => run_guard,
+        Ok(None) => {
+            log::info!("Skipping scheduled analysis, the run lock is held by another instance or run");
+            return vec![];
+        }
+        Err(e) => {
+            log::error!("Failed to acquire run lock: {}", e);
+            app_state.scheduler_monitor.record_run(RunOutcome::Failed, Some(format!("Failed to acquire run lock: {}", e)));
+            return vec![];
+        }
+    };
+
+    let started_at = bson::DateTime::now();
+    let context = RunContext::new(RunTrigger::Cron);
+    let analysis = lock_service.renew_during(AssertUnwindSafe(
+        app_state.service.med_service.analyze_doctors(doctors, context, false)
+    ).catch_unwind())
+        .await
+        .and_then(|analysis| analysis.unwrap_or_else(|_| Err(AppError::Internal("Analysis panicked".to_string()))));
+
+    let run = app_state.service.run_service
+        .record_run(context, started_at, &analysis)
+        .await;
+    match &run.error {
+        Some(e) => log::error!("Scheduled analysis {:?}: {}", run.outcome, e),
+        None => log::info!("Scheduled analysis succeeded"),
+    }
+    app_state.scheduler_monitor.record_run(run.outcome, run.error);
+
+    // Keep the lease for another ttl after the run, so replicas firing on the same tick
+    // (later because of jitter) still find it held
+    if let Err(e) = lock_service.acquire().await {
+        log::error!("Failed to renew run lock: {}", e);
+    }
+    analysis.unwrap_or_default()
+}
 
-        if let Some(datetime) = upcoming.next() {
-            if datetime.timestamp() <= local.timestamp() {
+/// Sends the pending digest on its own cron schedule, when the `digest` channel is enabled.
+/// Like analyses, only the replica holding the run lock sends it.
+pub async fn start_digest_scheduler(app_state: Data<AppState>) {
+    let Some(digest_service) = &app_state.service.digest_service else {
+        return;
+    };
+    let mut tick = Tick::cron(digest_service.schedule().clone(), Utc::now(), Duration::ZERO);
 
-                log::info!("Running schedule med bot");
-                app_state.service.med_service.analyze_appointment(&app_state.client)
-                    .await
-                    .expect("analyze appointment failed");
+    loop {
+        let now = Utc::now();
+        if tick.is_due(now) {
+            tick.advance(now, Duration::ZERO);
+            match app_state.service.lock_service.acquire().await {
+                Ok(true) => match digest_service.send_digest().await {
+                    Ok(sent) => log::info!("Digest sent with {} items", sent),
+                    Err(e) => log::error!("Failed to send digest: {}", e),
+                },
+                Ok(false) => log::info!("Skipping digest, the run lock is held by another instance"),
+                Err(e) => log::error!("Failed to acquire run lock for the digest: {}", e),
             }
+            continue;
+        }
+
+        let Some(fire_at) = tick.fire_at else {
+            log::warn!("Digest schedule has no upcoming tick, stopping the digest scheduler");
+            return;
+        };
+        actix_rt::time::sleep((fire_at - now).to_std().unwrap_or_default()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// At the top of every hour
+    fn hourly() -> Schedule {
+        Schedule::from_str("0 0 * * * *").unwrap()
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 10, 1, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn cron_tick_waits_for_the_next_tick() {
+        let tick = Tick::cron(hourly(), at(8, 30), Duration::ZERO);
+
+        assert_eq!(tick.fire_at, Some(at(9, 0)));
+        assert!(!tick.is_due(at(8, 59)));
+        assert!(tick.is_due(at(9, 0)));
+    }
+
+    #[test]
+    fn fired_tick_does_not_fire_twice() {
+        let mut tick = Tick::cron(hourly(), at(8, 30), Duration::ZERO);
+
+        assert!(tick.is_due(at(9, 0)));
+        tick.advance(at(9, 0), Duration::ZERO);
+
+        assert!(!tick.is_due(at(9, 0)));
+        assert!(!tick.is_due(at(9, 59)));
+        assert_eq!(tick.fire_at, Some(at(10, 0)));
+    }
+
+    #[test]
+    fn skipped_ticks_collapse_into_one_run() {
+        let mut tick = Tick::cron(hourly(), at(8, 30), Duration::ZERO);
+
+        // The 9:00, 10:00 and 11:00 ticks were missed while an analysis was running
+        let now = at(11, 20);
+        assert!(tick.is_due(now));
+        tick.advance(now, Duration::ZERO);
+
+        assert!(!tick.is_due(now));
+        assert_eq!(tick.fire_at, Some(at(12, 0)));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_bound() {
+        let jitter = Duration::from_secs(600);
+
+        for _ in 0..1000 {
+            let tick = Tick::cron(hourly(), at(8, 30), jitter);
+            let fire_at = tick.fire_at.unwrap();
+            assert!(fire_at >= at(9, 0) && fire_at <= at(9, 10), "{} out of bounds", fire_at);
+        }
+        assert_eq!(random_jitter(Duration::ZERO), chrono::Duration::zero());
+    }
+
+    #[test]
+    fn jittered_tick_does_not_fire_twice() {
+        let mut tick = Tick::cron(hourly(), at(8, 30), Duration::from_secs(600));
+        let fire_at = tick.fire_at.unwrap();
+
+        tick.advance(fire_at, Duration::from_secs(600));
+
+        assert!(tick.fire_at.unwrap() >= at(10, 0));
+    }
+
+    #[test]
+    fn adaptive_tick_waits_for_its_reschedule() {
+        let mut tick = Tick::adaptive(at(8, 30));
+        assert!(tick.is_due(at(8, 30)));
+
+        tick.advance(at(8, 30), Duration::ZERO);
+        assert!(!tick.is_due(at(23, 0)));
+
+        tick.reschedule(at(8, 35), Duration::from_secs(900), Duration::ZERO);
+        assert_eq!(tick.fire_at, Some(at(8, 50)));
+    }
+
+    fn monitor(failure_threshold: u32) -> SchedulerMonitor {
+        SchedulerMonitor {
+            failure_threshold,
+            status: RwLock::new(SchedulerStatus::default()),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn monitor_degrades_after_consecutive_failures() {
+        let monitor = monitor(3);
+
+        monitor.record_run(RunOutcome::Failed, Some("upstream down".to_string()));
+        monitor.record_run(RunOutcome::Failed, Some("upstream down".to_string()));
+        assert!(!monitor.status().degraded);
+
+        monitor.record_run(RunOutcome::Failed, Some("upstream down".to_string()));
+        let status = monitor.status();
+        assert!(status.degraded);
+        assert_eq!(status.consecutive_failures, 3);
+        assert_eq!(status.last_error.as_deref(), Some("upstream down"));
+        assert!(status.last_success_at.is_none());
+    }
+
+    #[test]
+    fn monitor_recovers_after_a_success() {
+        let monitor = monitor(2);
+        monitor.record_run(RunOutcome::Failed, Some("upstream down".to_string()));
+        monitor.record_run(RunOutcome::Failed, Some("upstream down".to_string()));
+        assert!(monitor.status().degraded);
+
+        monitor.record_run(RunOutcome::Success, None);
+
+        let status = monitor.status();
+        assert!(!status.degraded);
+        assert_eq!(status.consecutive_failures, 0);
+        assert_eq!((status.total_runs, status.total_failures), (3, 2));
+        assert!(status.last_error.is_none());
+        assert!(status.last_success_at.is_some());
+    }
+}
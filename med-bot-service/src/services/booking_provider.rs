@@ -0,0 +1,93 @@
+use crate::dto::appointment_model::AppointmentApiResponse;
+use crate::dto::search_model::SearchApiResponse;
+use crate::error::AppError;
+use crate::metrics::Metrics;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+
+/// Characters of a raw upstream body kept in decode errors
+const DECODE_ERROR_BODY_LIMIT: usize = 512;
+
+/// Source of doctor search results and appointment schedules
+#[async_trait(?Send)]
+pub trait BookingProvider: Send + Sync {
+    /// Provider name used in logs, e.g. `http` or `mock`
+    fn name(&self) -> &str;
+
+    /// `header_profile` names the request headers to send, `None` for the default profile
+    async fn search_med(&self, search_key: String, city_id: String, subject_id: String, header_profile: Option<&str>) -> Result<Vec<SearchApiResponse>, AppError>;
+
+    async fn get_appointments(&self, subject_id: String, doctor_id: String, service_id: String, partner_id: String, tree_id: Option<String>, header_profile: Option<&str>) -> Result<AppointmentApiResponse, AppError>;
+}
+
+/// Decodes an upstream body, keeping the failing field path and a truncated body on schema drift
+pub fn decode_response<T: DeserializeOwned>(metrics: &Metrics, endpoint: &str, status: u16, raw_json: &str) -> Result<T, AppError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(raw_json);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        metrics.record_decode_failure(endpoint);
+        let body = raw_json.chars().take(DECODE_ERROR_BODY_LIMIT).collect::<String>();
+        log::error!("Failed to decode {} response at `{}`: {}. Body: {}", endpoint, e.path(), e.inner(), body);
+
+        AppError::UpstreamDecode {
+            endpoint: endpoint.to_string(),
+            status,
+            path: e.path().to_string(),
+            message: e.inner().to_string(),
+            body,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The appointments fixture with a shift's `maxSlot` sent as a string
+    const MALFORMED_APPOINTMENTS: &str = include_str!("../../fixtures/malformed/appointments.json");
+
+    #[test]
+    fn decode_error_keeps_the_field_path() {
+        let metrics = Metrics::default();
+
+        let error = decode_response::<AppointmentApiResponse>(&metrics, "appointments", 200, MALFORMED_APPOINTMENTS).unwrap_err();
+
+        match error {
+            AppError::UpstreamDecode { endpoint, status, path, message, .. } => {
+                assert_eq!(endpoint, "appointments");
+                assert_eq!(status, 200);
+                assert_eq!(path, "days[0].shifts[0].maxSlot");
+                assert!(message.contains("invalid type"), "{}", message);
+            }
+            other => panic!("expected a decode error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decode_error_truncates_the_body() {
+        let metrics = Metrics::default();
+        assert!(MALFORMED_APPOINTMENTS.chars().count() > DECODE_ERROR_BODY_LIMIT);
+
+        let error = decode_response::<AppointmentApiResponse>(&metrics, "appointments", 200, MALFORMED_APPOINTMENTS).unwrap_err();
+
+        let AppError::UpstreamDecode { body, .. } = error else {
+            panic!("expected a decode error, got {:?}", error);
+        };
+        assert_eq!(body.chars().count(), DECODE_ERROR_BODY_LIMIT);
+        assert!(MALFORMED_APPOINTMENTS.starts_with(&body));
+    }
+
+    #[test]
+    fn decode_failures_are_counted_per_endpoint() {
+        let metrics = Metrics::default();
+
+        for _ in 0..2 {
+            let _ = decode_response::<AppointmentApiResponse>(&metrics, "appointments", 200, MALFORMED_APPOINTMENTS);
+        }
+        let _ = decode_response::<Vec<SearchApiResponse>>(&metrics, "search_med", 200, "{\"not\": \"a list\"}");
+        decode_response::<AppointmentApiResponse>(&metrics, "appointments", 200, include_str!("../../fixtures/appointments.json")).unwrap();
+
+        let decode_failures = metrics.snapshot().decode_failures;
+        assert_eq!(decode_failures.get("appointments"), Some(&2));
+        assert_eq!(decode_failures.get("search_med"), Some(&1));
+    }
+}
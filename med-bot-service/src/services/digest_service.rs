@@ -0,0 +1,204 @@
+use crate::config::digest_config::DigestConfig;
+use crate::error::AppError;
+use crate::models::doctor_appointment::AppointmentChange;
+use crate::models::documents::DigestItem;
+use crate::repositories::digest_repository::MongoDigestRepository;
+use crate::services::mail_service::MailService;
+use crate::services::notifier::{Notification, Notifier};
+use async_trait::async_trait;
+use cron::Schedule;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use mongodb::Collection;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+/// Changes of one doctor in a digest, by appointment date
+#[derive(Debug, Clone)]
+pub struct DoctorDigest {
+    pub doctor_name: String,
+    pub dates: Vec<(String, Vec<AppointmentChange>)>,
+}
+
+/// Collects changes as a notification channel and mails them together on the digest schedule
+#[derive(Debug, Clone)]
+pub struct DigestService {
+    mongo_digest_repository: MongoDigestRepository,
+    mail_service: MailService,
+    digest_config: DigestConfig,
+}
+
+impl DigestService {
+    pub fn builder(collection: Collection<DigestItem>, mail_service: MailService, digest_config: DigestConfig) -> DigestServiceBuilder {
+        DigestServiceBuilder::new(collection, mail_service, digest_config)
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.digest_config.schedule
+    }
+
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        self.mongo_digest_repository.ensure_indexes(self.digest_config.retention).await?;
+        Ok(())
+    }
+
+    /// Claims every pending item, mails them in one email and marks them sent; returns how many
+    /// were included. Claimed items are only picked up again once their claim is older than the
+    /// claim timeout, so failing to mark them sent doesn't send them twice on the next digest, and
+    /// items claimed by a digest that died are still sent.
+    pub async fn send_digest(&self) -> Result<usize, AppError> {
+        let claim_id = ObjectId::new();
+        let stale_before = DateTime::from_system_time(SystemTime::now() - self.digest_config.claim_timeout);
+        self.mongo_digest_repository.claim_pending(claim_id, DateTime::now(), stale_before).await?;
+        let items = self.mongo_digest_repository.find_claimed(claim_id).await?;
+        if items.is_empty() {
+            log::info!("No pending digest items");
+            return Ok(0);
+        }
+
+        let doctors = group_items(&items);
+        if let Err(e) = self.mail_service.send_digest(&self.digest_config.recipients, &doctors) {
+            if let Err(release_error) = self.mongo_digest_repository.release_claim(claim_id).await {
+                log::error!("Failed to release digest claim {}: {}", claim_id, release_error);
+            }
+            return Err(e);
+        }
+
+        if let Err(e) = self.mongo_digest_repository.mark_sent(claim_id, DateTime::now()).await {
+            log::error!("Digest {} was sent but its items could not be marked sent: {}", claim_id, e);
+        }
+        Ok(items.len())
+    }
+}
+
+/// Items by doctor name then appointment date, both sorted, keeping the item order within a date
+fn group_items(items: &[DigestItem]) -> Vec<DoctorDigest> {
+    let mut doctors: BTreeMap<String, BTreeMap<String, Vec<AppointmentChange>>> = BTreeMap::new();
+    for item in items {
+        doctors.entry(item.doctor_name.clone())
+            .or_default()
+            .entry(item.change.appointment.appointment_date.clone().unwrap_or_default())
+            .or_default()
+            .push(item.change.clone());
+    }
+
+    doctors.into_iter()
+        .map(|(doctor_name, dates)| DoctorDigest {
+            doctor_name,
+            dates: dates.into_iter().collect(),
+        })
+        .collect()
+}
+
+#[async_trait(?Send)]
+impl Notifier for DigestService {
+    fn name(&self) -> &str {
+        "digest"
+    }
+
+    fn recipient(&self) -> String {
+        self.digest_config.recipients.clone()
+    }
+
+    /// Stores the changes for the next digest
+    async fn notify(&self, notification: &Notification) -> Result<(), AppError> {
+        let found_at = DateTime::now();
+        let items = notification.changes.iter()
+            .map(|change| DigestItem {
+                id: None,
+                doctor_ref_id: notification.doctor_ref_id.clone(),
+                doctor_name: notification.doctor_name.clone(),
+                change: change.clone(),
+                found_at,
+                sending_at: None,
+                claim_id: None,
+                sent_at: None,
+            })
+            .collect::<Vec<DigestItem>>();
+
+        self.mongo_digest_repository.insert_items(&items).await?;
+        Ok(())
+    }
+}
+
+
+pub struct DigestServiceBuilder {
+    mongo_digest_repository: MongoDigestRepository,
+    mail_service: MailService,
+    digest_config: DigestConfig,
+}
+
+impl DigestServiceBuilder {
+    pub fn new(collection: Collection<DigestItem>, mail_service: MailService, digest_config: DigestConfig) -> DigestServiceBuilder {
+        DigestServiceBuilder {
+            mongo_digest_repository: MongoDigestRepository::builder(collection).build(),
+            mail_service,
+            digest_config,
+        }
+    }
+
+    pub fn build(self) -> DigestService {
+        DigestService {
+            mongo_digest_repository: self.mongo_digest_repository,
+            mail_service: self.mail_service,
+            digest_config: self.digest_config,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::doctor_appointment::{AppointmentPicking, ChangeType};
+
+    fn item(doctor_name: &str, appointment_date: &str, shift_code: &str) -> DigestItem {
+        DigestItem {
+            id: None,
+            doctor_ref_id: format!("ref-{}", doctor_name),
+            doctor_name: doctor_name.to_string(),
+            change: AppointmentChange {
+                change_type: ChangeType::Opened,
+                appointment: AppointmentPicking {
+                    appointment_date: Some(appointment_date.to_string()),
+                    shift_code: Some(shift_code.to_string()),
+                    ..AppointmentPicking::default()
+                },
+            },
+            found_at: DateTime::now(),
+            sending_at: None,
+            claim_id: None,
+            sent_at: None,
+        }
+    }
+
+    fn shift_codes(changes: &[AppointmentChange]) -> Vec<&str> {
+        changes.iter().filter_map(|change| change.appointment.shift_code.as_deref()).collect()
+    }
+
+    #[test]
+    fn groups_items_by_doctor_then_date() {
+        let items = [
+            item("Tran Thi B", "2024-10-03", "MORNING"),
+            item("Nguyen Van A", "2024-10-03", "AFTERNOON"),
+            item("Nguyen Van A", "2024-10-02", "MORNING"),
+            item("Nguyen Van A", "2024-10-03", "MORNING"),
+        ];
+
+        let doctors = group_items(&items);
+
+        assert_eq!(doctors.len(), 2);
+        assert_eq!(doctors[0].doctor_name, "Nguyen Van A");
+        assert_eq!(doctors[0].dates.len(), 2);
+        assert_eq!(doctors[0].dates[0].0, "2024-10-02");
+        assert_eq!(shift_codes(&doctors[0].dates[0].1), ["MORNING"]);
+        assert_eq!(doctors[0].dates[1].0, "2024-10-03");
+        assert_eq!(shift_codes(&doctors[0].dates[1].1), ["AFTERNOON", "MORNING"]);
+        assert_eq!(doctors[1].doctor_name, "Tran Thi B");
+        assert_eq!(shift_codes(&doctors[1].dates[0].1), ["MORNING"]);
+    }
+
+    #[test]
+    fn no_items_no_doctors() {
+        assert!(group_items(&[]).is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::config::header_profile_config::HeaderProfiles;
+use crate::dto::doctor_model::{DoctorPatchRequest, DoctorQuery};
+use crate::error::AppError;
+use crate::models::doctor_appointment::AppointmentWindow;
+use crate::models::documents::Doctor;
+use crate::repositories::doctor_repository::MongoDoctorRepository;
+use crate::repositories::is_duplicate_key;
+use chrono::Local;
+use cron::Schedule;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, Document};
+use mongodb::Collection;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct DoctorService {
+    mongo_doctor_repository: MongoDoctorRepository,
+    header_profiles: HeaderProfiles,
+}
+
+impl DoctorService {
+    pub fn builder(collection: Collection<Doctor>) -> DoctorServiceBuilder {
+        DoctorServiceBuilder::new(collection)
+    }
+
+    /// Checks the ids are present, the window, weekday and time fields parse and the header profile exists
+    pub fn validate(&self, doctor: &Doctor) -> Result<(), AppError> {
+        let required = [
+            ("doctor_ref_id", &doctor.doctor_ref_id),
+            ("doctor_name", &doctor.doctor_name),
+            ("subject_ref_id", &doctor.subject_ref_id),
+            ("subject_name", &doctor.subject_name),
+            ("service_name", &doctor.service_name),
+            ("hospital_id", &doctor.hospital_id),
+            ("city_id", &doctor.city_id),
+        ];
+
+        let missing = required.iter()
+            .filter(|(_, value)| value.trim().is_empty())
+            .map(|(field, _)| *field)
+            .collect::<Vec<&str>>();
+        if !missing.is_empty() {
+            return Err(AppError::DoctorValidation(format!("Missing required fields: {}", missing.join(", "))));
+        }
+
+        AppointmentWindow::from_doctor(doctor, Local::now().date_naive())
+            .map_err(AppError::DoctorValidation)?;
+
+        if let Some(poll_cron) = &doctor.poll_cron {
+            Schedule::from_str(poll_cron)
+                .map_err(|e| AppError::DoctorValidation(format!("Invalid poll_cron {}: {}", poll_cron, e)))?;
+        }
+
+        if let Some(header_profile) = &doctor.header_profile {
+            if !self.header_profiles.contains(header_profile) {
+                return Err(AppError::DoctorValidation(format!("Unknown header_profile {}", header_profile)));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        self.mongo_doctor_repository.ensure_indexes().await?;
+        Ok(())
+    }
+
+    pub async fn create_doctor(&self, mut doctor: Doctor) -> Result<Doctor, AppError> {
+        doctor.id = None;
+        let result = self.mongo_doctor_repository.insert_doctor(&doctor).await
+            .map_err(|e| Self::window_conflict(e, &doctor))?;
+        doctor.id = result.inserted_id.as_object_id();
+        Ok(doctor)
+    }
+
+    pub async fn get_doctor(&self, id: ObjectId) -> Result<Doctor, AppError> {
+        self.mongo_doctor_repository.get_doctor_by_id(id).await?
+            .ok_or_else(|| AppError::NotFound(format!("Doctor {}", id)))
+    }
+
+    pub async fn list_doctors(&self, query: &DoctorQuery) -> Result<Vec<Doctor>, AppError> {
+        let mut filter = Document::new();
+        if let Some(city_id) = &query.city_id {
+            filter.insert("city_id", city_id);
+        }
+        if let Some(hospital_id) = &query.hospital_id {
+            filter.insert("hospital_id", hospital_id);
+        }
+        if let Some(active) = query.active {
+            filter.insert("active", active);
+        }
+        if let Some(current_target) = query.current_target {
+            filter.insert("current_target", current_target);
+        }
+
+        Ok(self.mongo_doctor_repository.find_doctors(filter).await?)
+    }
+
+    pub async fn replace_doctor(&self, id: ObjectId, mut doctor: Doctor) -> Result<Doctor, AppError> {
+        doctor.id = Some(id);
+        let result = self.mongo_doctor_repository.replace_doctor(id, &doctor).await
+            .map_err(|e| Self::window_conflict(e, &doctor))?;
+        if result.matched_count == 0 {
+            return Err(AppError::NotFound(format!("Doctor {}", id)));
+        }
+        Ok(doctor)
+    }
+
+    /// Validates the patched document, then writes only the patched fields
+    pub async fn patch_doctor(&self, id: ObjectId, patch: DoctorPatchRequest) -> Result<Doctor, AppError> {
+        let mut doctor = self.get_doctor(id).await?;
+        let update = patch.to_update()?;
+        if update.is_empty() {
+            return Ok(doctor);
+        }
+
+        // Validate the merged document so a patch can't leave the target half configured
+        patch.apply(&mut doctor);
+        self.validate(&doctor)?;
+
+        self.mongo_doctor_repository.patch_doctor(id, update).await
+            .map_err(|e| Self::window_conflict(e, &doctor))?
+            .ok_or_else(|| AppError::NotFound(format!("Doctor {}", id)))
+    }
+
+    pub async fn delete_doctor(&self, id: ObjectId) -> Result<(), AppError> {
+        let result = self.mongo_doctor_repository.delete_doctor(id).await?;
+        if result.deleted_count == 0 {
+            return Err(AppError::NotFound(format!("Doctor {}", id)));
+        }
+        Ok(())
+    }
+
+    pub async fn set_active(&self, id: ObjectId, active: bool) -> Result<Doctor, AppError> {
+        self.update_flags(id, doc! {"active": active}).await
+    }
+
+    pub async fn set_current_target(&self, id: ObjectId, current_target: bool) -> Result<Doctor, AppError> {
+        self.update_flags(id, doc! {"current_target": current_target}).await
+    }
+
+    async fn update_flags(&self, id: ObjectId, update: Document) -> Result<Doctor, AppError> {
+        let result = self.mongo_doctor_repository.update_doctor(id, update).await?;
+        if result.matched_count == 0 {
+            return Err(AppError::NotFound(format!("Doctor {}", id)));
+        }
+        self.get_doctor(id).await
+    }
+
+    fn window_conflict(error: mongodb::error::Error, doctor: &Doctor) -> AppError {
+        if is_duplicate_key(&error) {
+            return AppError::Conflict(format!(
+                "Doctor {} is already tracked for {} / {} at {} in this date window",
+                doctor.doctor_ref_id, doctor.subject_name, doctor.service_name, doctor.hospital_id
+            ));
+        }
+        AppError::Mongo(error)
+    }
+}
+
+pub struct DoctorServiceBuilder {
+    mongo_doctor_repository: MongoDoctorRepository,
+    header_profiles: Option<HeaderProfiles>,
+}
+
+impl DoctorServiceBuilder {
+    pub fn new(collection: Collection<Doctor>) -> DoctorServiceBuilder {
+        let mongo_doctor_repository = MongoDoctorRepository::builder(collection).build();
+        DoctorServiceBuilder {
+            mongo_doctor_repository,
+            header_profiles: None,
+        }
+    }
+
+    pub fn with_header_profiles(mut self, header_profiles: HeaderProfiles) -> DoctorServiceBuilder {
+        self.header_profiles = Some(header_profiles);
+        self
+    }
+
+    pub fn build(self) -> DoctorService {
+        DoctorService {
+            mongo_doctor_repository: self.mongo_doctor_repository,
+            header_profiles: self.header_profiles.expect("Header profiles not initialized"),
+        }
+    }
+}
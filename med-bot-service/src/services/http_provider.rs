@@ -0,0 +1,168 @@
+use crate::config::header_profile_config::HeaderProfile;
+use crate::config::med_target_config::MedTarget;
+use crate::dto::appointment_model::AppointmentApiResponse;
+use crate::dto::search_model::SearchApiResponse;
+use crate::error::AppError;
+use crate::metrics::Metrics;
+use crate::rate_limit::{parse_retry_after, RateLimiter};
+use crate::services::booking_provider::{decode_response, BookingProvider};
+use async_trait::async_trait;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, RequestBuilder, Response};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Calls the live booking site
+pub struct HttpBookingProvider {
+    client: Client,
+    med_target: MedTarget,
+    metrics: Arc<Metrics>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl HttpBookingProvider {
+    pub fn builder(client: Client, med_target: MedTarget) -> HttpBookingProviderBuilder {
+        HttpBookingProviderBuilder::new(client, med_target)
+    }
+
+    /// Rate limiting and server errors are reported as such rather than as undecodable bodies.
+    /// Their `Retry-After` holds back every following request, not only the retry of this one.
+    fn check_status(&self, endpoint: &str, response: &Response) -> Result<(), AppError> {
+        let status = response.status().as_u16();
+        if status == 429 || status >= 500 {
+            if let Some(retry_after) = response.headers().get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_retry_after) {
+                self.rate_limiter.defer(retry_after);
+            }
+            return Err(AppError::UpstreamStatus {
+                endpoint: endpoint.to_string(),
+                status,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl BookingProvider for HttpBookingProvider {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn search_med(&self, search_key: String, city_id: String, subject_id: String, header_profile: Option<&str>) -> Result<Vec<SearchApiResponse>, AppError> {
+        let mut map = HashMap::new();
+        map.insert("search_key", search_key);
+        map.insert("category", String::from("doctor"));
+        map.insert("city_id", city_id);
+        map.insert("limit", String::from("3"));
+        map.insert("offset", String::from("1"));
+        map.insert("subject_ids", subject_id);
+
+        let (profile_name, profile) = self.med_target.header_profiles.resolve(header_profile);
+        log::debug!("search_med with header profile {}: {:?}", profile_name, profile);
+
+        let request = self.client.post(self.med_target.search_med_api.clone())
+            .header("Content-Type", "application/json;charset=utf-8")
+            .header("platform", profile.search_platform.clone())
+            .header("Origin", self.med_target.origin_header.clone())
+            .header("Referer", self.med_target.origin_header.clone());
+
+        let _permit = self.rate_limiter.acquire().await?;
+        let result = with_profile(request, profile)
+            .json(&map)
+            .send()
+            .await?;
+
+        // Extract the response body as text
+        let status = result.status().as_u16();
+        self.check_status("search_med", &result)?;
+        let raw_json = result.text().await?;
+        // println!("Raw JSON response: {}", raw_json);
+
+        // Deserialize the JSON
+        decode_response(&self.metrics, "search_med", status, &raw_json)
+    }
+
+    async fn get_appointments(&self, subject_id: String, doctor_id: String, service_id: String, partner_id: String, tree_id: Option<String>, header_profile: Option<&str>) -> Result<AppointmentApiResponse, AppError> {
+        let mut map = HashMap::new();
+        map.insert("subjectId", subject_id);
+        map.insert("doctorId", doctor_id);
+        map.insert("serviceId", service_id);
+        map.insert("treeId", tree_id.unwrap_or_else(|| String::from("DATE")));
+
+        let (profile_name, profile) = self.med_target.header_profiles.resolve(header_profile);
+        log::debug!("appointments with header profile {}: {:?}", profile_name, profile);
+
+        let request = self.client.post(self.med_target.appointment_api.clone())
+            .header("Content-Type", "application/json;charset=utf-8")
+            .header("partnerid", partner_id)
+            .header("appid", self.med_target.appid_header.clone())
+            .header("platform", profile.appointment_platform.clone())
+            .header("Origin", self.med_target.origin_header.clone())
+            .header("Referer", self.med_target.origin_header.clone());
+
+        let _permit = self.rate_limiter.acquire().await?;
+        let result = with_profile(request, profile)
+            .json(&map)
+            .send()
+            .await?;
+
+        // Extract the response body as text
+        let status = result.status().as_u16();
+        self.check_status("appointments", &result)?;
+        let raw_json = result.text().await?;
+        // println!("Raw JSON response: {}", raw_json);
+
+        // Deserialize the JSON
+        decode_response(&self.metrics, "appointments", status, &raw_json)
+    }
+}
+
+/// Adds the browser identity of `profile`; the platform header differs per endpoint and is set by the caller
+fn with_profile(mut request: RequestBuilder, profile: &HeaderProfile) -> RequestBuilder {
+    request = request
+        .header("User-Agent", profile.user_agent.clone())
+        .header("locale", profile.locale.clone());
+    for (name, value) in &profile.headers {
+        request = request.header(name, value);
+    }
+    request
+}
+
+pub struct HttpBookingProviderBuilder {
+    client: Client,
+    med_target: MedTarget,
+    metrics: Option<Arc<Metrics>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl HttpBookingProviderBuilder {
+    pub fn new(client: Client, med_target: MedTarget) -> HttpBookingProviderBuilder {
+        HttpBookingProviderBuilder {
+            client,
+            med_target,
+            metrics: None,
+            rate_limiter: None,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> HttpBookingProviderBuilder {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> HttpBookingProviderBuilder {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn build(self) -> HttpBookingProvider {
+        HttpBookingProvider {
+            client: self.client,
+            med_target: self.med_target,
+            metrics: self.metrics.expect("Metrics not initialized"),
+            rate_limiter: self.rate_limiter.expect("Rate limiter not initialized"),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::dto::appointment_model::TimeSlot;
+use crate::dto::ledger_model::LedgerQuery;
+use crate::error::AppError;
+use crate::models::doctor_appointment::{AppointmentChange, ChangeType};
+use crate::models::documents::{Doctor, NotificationLedgerEntry};
+use crate::repositories::ledger_repository::MongoLedgerRepository;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, Document};
+use mongodb::Collection;
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone)]
+pub struct LedgerService {
+    mongo_ledger_repository: MongoLedgerRepository,
+    cooldown: Duration,
+}
+
+impl LedgerService {
+    pub fn builder(collection: Collection<NotificationLedgerEntry>, cooldown: Duration) -> LedgerServiceBuilder {
+        LedgerServiceBuilder::new(collection, cooldown)
+    }
+
+    pub async fn ensure_indexes(&self, retention: Duration) -> Result<(), AppError> {
+        self.mongo_ledger_repository.ensure_indexes(retention).await?;
+        Ok(())
+    }
+
+    /// Drops the slots `channel` was notified about within the cooldown, whether they opened again or closed,
+    /// and the changes left without slots, so a slot flickering between free and full alerts once per cooldown
+    pub async fn hold_back(&self, doctor: &Doctor, channel: &str, changes: &[AppointmentChange]) -> Result<Vec<AppointmentChange>, AppError> {
+        if self.cooldown.is_zero() {
+            return Ok(changes.to_vec());
+        }
+
+        let since = DateTime::from_system_time(SystemTime::now() - self.cooldown);
+        let notified = self.mongo_ledger_repository
+            .find_notified_since(target_id(doctor)?, channel, since)
+            .await?
+            .into_iter()
+            .map(|entry| (entry.appointment_date, entry.time_id))
+            .collect::<HashSet<(String, String)>>();
+
+        Ok(without_notified(changes, &notified))
+    }
+
+    /// Marks every free slot of `changes` as notified on `channel` now
+    pub async fn record(&self, doctor: &Doctor, channel: &str, recipient: &str, changes: &[AppointmentChange]) -> Result<(), AppError> {
+        let target_id = target_id(doctor)?;
+        let now = DateTime::now();
+
+        for (change, slot) in tracked_slots(changes) {
+            let entry = NotificationLedgerEntry {
+                id: None,
+                target_id,
+                doctor_ref_id: doctor.doctor_ref_id.clone(),
+                doctor_name: doctor.doctor_name.clone(),
+                appointment_date: change.appointment.appointment_date.clone().unwrap_or_default(),
+                time_id: slot.time_id.clone(),
+                channel: channel.to_string(),
+                recipient: recipient.to_string(),
+                change_type: change.change_type,
+                shift_code: change.appointment.shift_code.clone(),
+                start_time: slot.start_time.clone(),
+                available_slot: slot.available_slot,
+                last_notified_at: now,
+                notify_count: 1,
+            };
+            self.mongo_ledger_repository.upsert_entry(&entry).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn list_entries(&self, query: &LedgerQuery) -> Result<Vec<NotificationLedgerEntry>, AppError> {
+        let mut filter = Document::new();
+        if let Some(target_id) = query.target_id()? {
+            filter.insert("target_id", target_id);
+        }
+        if let Some(channel) = &query.channel {
+            filter.insert("channel", channel);
+        }
+
+        Ok(self.mongo_ledger_repository.find_entries(filter, query.limit()).await?)
+    }
+}
+
+/// `changes` without the slots in `notified`, keyed by appointment date and `time_id`. Full slots of
+/// opened or changed shifts were never notified as free, so they are kept.
+fn without_notified(changes: &[AppointmentChange], notified: &HashSet<(String, String)>) -> Vec<AppointmentChange> {
+    changes.iter()
+        .filter_map(|change| {
+            let date = change.appointment.appointment_date.clone().unwrap_or_default();
+            let slots = change.appointment.available_slot.as_ref()?.iter()
+                .filter(|slot| {
+                    let held_back = !is_tracked(change) || is_free(slot.available_slot);
+                    !held_back || !notified.contains(&(date.clone(), slot.time_id.clone()))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            if slots.is_empty() {
+                return None;
+            }
+
+            let mut change = change.clone();
+            change.appointment.available_slot = Some(slots);
+            Some(change)
+        })
+        .collect()
+}
+
+/// The free slots of opened or changed shifts, the ones recorded as notified
+fn tracked_slots(changes: &[AppointmentChange]) -> impl Iterator<Item = (&AppointmentChange, &TimeSlot)> {
+    changes.iter()
+        .filter(|change| is_tracked(change))
+        .flat_map(|change| change.appointment.available_slot.iter().flatten().map(move |slot| (change, slot)))
+        .filter(|(_, slot)| is_free(slot.available_slot))
+}
+
+/// Closing is never recorded, so it never holds a reopening back; it is only held back itself
+fn is_tracked(change: &AppointmentChange) -> bool {
+    matches!(change.change_type, ChangeType::Opened | ChangeType::CountChanged)
+}
+
+fn is_free(available_slot: Option<u32>) -> bool {
+    available_slot.is_some_and(|available_slot| available_slot > 0)
+}
+
+fn target_id(doctor: &Doctor) -> Result<ObjectId, AppError> {
+    doctor.id.ok_or_else(|| AppError::DoctorValidation(format!("Target {} has no id", doctor.doctor_name)))
+}
+
+
+pub struct LedgerServiceBuilder {
+    mongo_ledger_repository: MongoLedgerRepository,
+    cooldown: Duration,
+}
+
+impl LedgerServiceBuilder {
+    pub fn new(collection: Collection<NotificationLedgerEntry>, cooldown: Duration) -> LedgerServiceBuilder {
+        LedgerServiceBuilder {
+            mongo_ledger_repository: MongoLedgerRepository::builder(collection).build(),
+            cooldown,
+        }
+    }
+
+    pub fn build(self) -> LedgerService {
+        LedgerService {
+            mongo_ledger_repository: self.mongo_ledger_repository,
+            cooldown: self.cooldown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::doctor_appointment::AppointmentPicking;
+
+    fn change(change_type: ChangeType, time_ids: &[(&str, u32)]) -> AppointmentChange {
+        let slots = time_ids.iter()
+            .map(|(time_id, available_slot)| TimeSlot {
+                time_id: time_id.to_string(),
+                available_slot: Some(*available_slot),
+                max_slot: Some(4),
+                start_time: "07:30".to_string(),
+                end_time: "08:00".to_string(),
+                room_id: "room-001".to_string(),
+                priority_room: 0,
+            })
+            .collect();
+
+        AppointmentChange {
+            change_type,
+            appointment: AppointmentPicking {
+                appointment_date: Some("2024-10-02".to_string()),
+                shift_code: Some("MORNING".to_string()),
+                available_slot: Some(slots),
+                ..AppointmentPicking::default()
+            },
+        }
+    }
+
+    fn notified(time_ids: &[&str]) -> HashSet<(String, String)> {
+        time_ids.iter()
+            .map(|time_id| ("2024-10-02".to_string(), time_id.to_string()))
+            .collect()
+    }
+
+    fn time_ids(change: &AppointmentChange) -> Vec<&str> {
+        change.appointment.available_slot.iter().flatten()
+            .map(|slot| slot.time_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn notified_free_slots_are_held_back() {
+        let changes = [change(ChangeType::Opened, &[("slot-001", 2), ("slot-002", 1)])];
+
+        let changes = without_notified(&changes, &notified(&["slot-001"]));
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(time_ids(&changes[0]), vec!["slot-002"]);
+    }
+
+    #[test]
+    fn change_without_slots_left_is_dropped() {
+        let changes = [change(ChangeType::CountChanged, &[("slot-001", 1)])];
+
+        assert!(without_notified(&changes, &notified(&["slot-001"])).is_empty());
+    }
+
+    #[test]
+    fn closed_slots_notified_within_the_cooldown_are_held_back() {
+        let changes = [change(ChangeType::Closed, &[("slot-001", 2), ("slot-002", 1)])];
+
+        let changes = without_notified(&changes, &notified(&["slot-001"]));
+
+        assert_eq!(time_ids(&changes[0]), vec!["slot-002"]);
+    }
+
+    #[test]
+    fn closed_slots_are_not_recorded() {
+        let changes = [
+            change(ChangeType::Opened, &[("slot-001", 2), ("slot-002", 0)]),
+            change(ChangeType::Closed, &[("slot-003", 1)]),
+        ];
+
+        let recorded = tracked_slots(&changes)
+            .map(|(_, slot)| slot.time_id.as_str())
+            .collect::<Vec<&str>>();
+
+        assert_eq!(recorded, vec!["slot-001"]);
+    }
+
+    #[test]
+    fn flickering_slot_alerts_once_within_the_cooldown() {
+        // free -> full -> free -> full, each run diffed against the previous one
+        let runs = [
+            change(ChangeType::Opened, &[("slot-001", 2)]),
+            change(ChangeType::Closed, &[("slot-001", 2)]),
+            change(ChangeType::Opened, &[("slot-001", 1)]),
+            change(ChangeType::Closed, &[("slot-001", 1)]),
+        ];
+
+        let mut ledger = HashSet::new();
+        let mut alerts = 0;
+        for run in runs {
+            let sent = without_notified(&[run], &ledger);
+            if !sent.is_empty() {
+                alerts += 1;
+            }
+            ledger.extend(tracked_slots(&sent).map(|(change, slot)| {
+                (change.appointment.appointment_date.clone().unwrap_or_default(), slot.time_id.clone())
+            }));
+        }
+
+        assert_eq!(alerts, 1);
+    }
+}
@@ -0,0 +1,309 @@
+use crate::error::AppError;
+use crate::models::documents::RunLock;
+use crate::repositories::lock_repository::{LockStore, MongoLockRepository};
+use futures::future::{self, Either};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use mongodb::Collection;
+use std::env;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+/// `_id` of the lock document guarding scheduled runs
+const SCHEDULER_LOCK: &str = "scheduler";
+
+#[derive(Debug, Clone)]
+pub struct LockService {
+    lock_store: Arc<dyn LockStore>,
+    /// Identifies this instance: host name plus a per process id, so restarts get a new owner
+    owner: String,
+    ttl: Duration,
+    /// Held while an analysis runs on this instance, the shared lock alone can't tell our runs apart
+    running: Arc<Mutex<()>>,
+}
+
+/// Held for the whole analysis run
+pub struct RunGuard {
+    _running: OwnedMutexGuard<()>,
+}
+
+impl LockService {
+    pub fn builder(collection: Collection<RunLock>, ttl: Duration) -> LockServiceBuilder {
+        LockServiceBuilder::new(collection, ttl)
+    }
+
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Whether this instance now holds the scheduler lock for the next `ttl`. Calling it again
+    /// while holding the lock extends the lease.
+    pub async fn acquire(&self) -> Result<bool, AppError> {
+        let expires_at = DateTime::from_system_time(SystemTime::now() + self.ttl);
+        let lock = self.lock_store
+            .try_acquire(SCHEDULER_LOCK, &self.owner, expires_at)
+            .await?;
+        Ok(lock.is_some())
+    }
+
+    /// Runs `run` while renewing the lease every third of the ttl, so a run outlasting the ttl keeps
+    /// the lock. When a renewal fails or another instance took the lock over, `run` is stopped.
+    pub async fn renew_during<F: Future>(&self, run: F) -> Result<F::Output, AppError> {
+        let heartbeat = async {
+            loop {
+                actix_rt::time::sleep(self.ttl / 3).await;
+                match self.acquire().await {
+                    Ok(true) => log::debug!("Renewed run lock for {}", self.owner),
+                    Ok(false) => {
+                        let holder = self.current_lock().await.ok().flatten()
+                            .map(|lock| lock.owner)
+                            .unwrap_or_else(|| "another instance".to_string());
+                        return AppError::Conflict(format!("The run lock was taken over by {}", holder));
+                    }
+                    Err(e) => return e,
+                }
+            }
+        };
+
+        match future::select(pin!(run), pin!(heartbeat)).await {
+            Either::Left((output, _)) => Ok(output),
+            Either::Right((e, _)) => {
+                log::error!("Stopping the analysis run, failed to renew the run lock: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Takes the run lock for an analysis; `None` when another instance holds it or an
+    /// analysis is already running here
+    pub async fn try_lock_run(&self) -> Result<Option<RunGuard>, AppError> {
+        let Ok(running) = self.running.clone().try_lock_owned() else {
+            return Ok(None);
+        };
+        if !self.acquire().await? {
+            return Ok(None);
+        }
+        Ok(Some(RunGuard { _running: running }))
+    }
+
+    /// Like `try_lock_run`, with a conflict naming the holder when the lock is taken
+    pub async fn lock_run(&self) -> Result<RunGuard, AppError> {
+        if let Some(guard) = self.try_lock_run().await? {
+            return Ok(guard);
+        }
+
+        let holder = self.current_lock().await?
+            .map(|lock| lock.owner)
+            .unwrap_or_else(|| self.owner.clone());
+        Err(AppError::Conflict(format!("An analysis run is in progress, the run lock is held by {}", holder)))
+    }
+
+    pub async fn current_lock(&self) -> Result<Option<RunLock>, AppError> {
+        self.lock_store.get_lock(SCHEDULER_LOCK).await
+    }
+}
+
+
+pub struct LockServiceBuilder {
+    lock_store: Arc<dyn LockStore>,
+    owner: String,
+    ttl: Duration,
+}
+
+impl LockServiceBuilder {
+    pub fn new(collection: Collection<RunLock>, ttl: Duration) -> LockServiceBuilder {
+        let hostname = env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string());
+
+        LockServiceBuilder {
+            lock_store: Arc::new(MongoLockRepository::builder(collection).build()),
+            owner: format!("{}-{}", hostname, ObjectId::new().to_hex()),
+            ttl,
+        }
+    }
+
+    /// Keeps the leases in `lock_store` instead of the Mongo collection
+    #[cfg(test)]
+    pub fn with_lock_store(mut self, lock_store: Arc<dyn LockStore>) -> LockServiceBuilder {
+        self.lock_store = lock_store;
+        self
+    }
+
+    pub fn build(self) -> LockService {
+        LockService {
+            lock_store: self.lock_store,
+            owner: self.owner,
+            ttl: self.ttl,
+            running: Arc::new(Mutex::new(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use mongodb::Client;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// In-memory lease, with switches to fail renewals or hand the lock to another owner
+    #[derive(Debug, Default)]
+    struct FakeLockStore {
+        lock: std::sync::Mutex<Option<RunLock>>,
+        acquisitions: AtomicUsize,
+        failing: AtomicBool,
+    }
+
+    impl FakeLockStore {
+        fn hand_over(&self, owner: &str) {
+            *self.lock.lock().unwrap() = Some(RunLock {
+                id: SCHEDULER_LOCK.to_string(),
+                owner: owner.to_string(),
+                acquired_at: DateTime::now(),
+                expires_at: DateTime::from_system_time(SystemTime::now() + Duration::from_secs(600)),
+            });
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl LockStore for FakeLockStore {
+        async fn try_acquire(&self, name: &str, owner: &str, expires_at: DateTime) -> Result<Option<RunLock>, AppError> {
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(AppError::Internal("lock store unavailable".to_string()));
+            }
+            self.acquisitions.fetch_add(1, Ordering::SeqCst);
+
+            let mut lock = self.lock.lock().unwrap();
+            let now = DateTime::now();
+            if lock.as_ref().is_some_and(|held| held.owner != owner && held.expires_at > now) {
+                return Ok(None);
+            }
+            *lock = Some(RunLock {
+                id: name.to_string(),
+                owner: owner.to_string(),
+                acquired_at: now,
+                expires_at,
+            });
+            Ok(lock.clone())
+        }
+
+        async fn get_lock(&self, _name: &str) -> Result<Option<RunLock>, AppError> {
+            Ok(self.lock.lock().unwrap().clone())
+        }
+    }
+
+    async fn lock_service(ttl: Duration) -> (LockService, Arc<FakeLockStore>) {
+        let collection = Client::with_uri_str("mongodb://localhost:27017").await.unwrap()
+            .database("med_bot_test")
+            .collection("run_lock");
+        let lock_store = Arc::new(FakeLockStore::default());
+        let lock_service = LockService::builder(collection, ttl)
+            .with_lock_store(lock_store.clone())
+            .build();
+        (lock_service, lock_store)
+    }
+
+    #[actix_rt::test]
+    async fn try_lock_run_refuses_a_second_run_here() {
+        let (lock_service, _) = lock_service(Duration::from_secs(60)).await;
+
+        let run_guard = lock_service.try_lock_run().await.unwrap();
+        assert!(run_guard.is_some());
+        assert!(lock_service.try_lock_run().await.unwrap().is_none());
+
+        drop(run_guard);
+        assert!(lock_service.try_lock_run().await.unwrap().is_some());
+    }
+
+    #[actix_rt::test]
+    async fn try_lock_run_refuses_a_lock_held_elsewhere() {
+        let (lock_service, lock_store) = lock_service(Duration::from_secs(60)).await;
+        lock_store.hand_over("replica-b");
+
+        assert!(lock_service.try_lock_run().await.unwrap().is_none());
+    }
+
+    #[actix_rt::test]
+    async fn lock_run_names_the_holder() {
+        let (lock_service, lock_store) = lock_service(Duration::from_secs(60)).await;
+        lock_store.hand_over("replica-b");
+
+        match lock_service.lock_run().await {
+            Err(AppError::Conflict(message)) => assert!(message.contains("replica-b")),
+            other => panic!("expected a conflict, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn lease_is_renewed_during_a_long_run() {
+        let (lock_service, lock_store) = lock_service(Duration::from_millis(30)).await;
+        let _run_guard = lock_service.lock_run().await.unwrap();
+
+        let output = lock_service.renew_during(async {
+            actix_rt::time::sleep(Duration::from_millis(100)).await;
+            "done"
+        }).await;
+
+        assert_eq!(output.unwrap(), "done");
+        // The first acquisition plus at least one renewal per 10ms
+        assert!(lock_store.acquisitions.load(Ordering::SeqCst) >= 4);
+        assert_eq!(lock_store.get_lock(SCHEDULER_LOCK).await.unwrap().unwrap().owner, lock_service.owner());
+    }
+
+    #[actix_rt::test]
+    async fn run_stops_when_the_lock_is_taken_over() {
+        let (lock_service, lock_store) = lock_service(Duration::from_millis(30)).await;
+        let _run_guard = lock_service.lock_run().await.unwrap();
+        lock_store.hand_over("replica-b");
+
+        let finished = Cell::new(false);
+        let output = lock_service.renew_during(async {
+            actix_rt::time::sleep(Duration::from_millis(200)).await;
+            finished.set(true);
+        }).await;
+
+        assert!(matches!(output, Err(AppError::Conflict(message)) if message.contains("replica-b")));
+        assert!(!finished.get());
+    }
+
+    #[actix_rt::test]
+    async fn run_stops_when_a_renewal_fails() {
+        let (lock_service, lock_store) = lock_service(Duration::from_millis(30)).await;
+        let _run_guard = lock_service.lock_run().await.unwrap();
+        lock_store.failing.store(true, Ordering::SeqCst);
+
+        let finished = Cell::new(false);
+        let output = lock_service.renew_during(async {
+            actix_rt::time::sleep(Duration::from_millis(200)).await;
+            finished.set(true);
+        }).await;
+
+        assert!(matches!(output, Err(AppError::Internal(_))));
+        assert!(!finished.get());
+    }
+
+    #[actix_rt::test]
+    async fn overlapping_ticks_run_one_analysis_at_a_time() {
+        let (lock_service, _) = lock_service(Duration::from_secs(60)).await;
+        let runs = AtomicUsize::new(0);
+
+        let tick = || async {
+            let Some(_run_guard) = lock_service.try_lock_run().await.unwrap() else {
+                return;
+            };
+            lock_service.renew_during(async {
+                runs.fetch_add(1, Ordering::SeqCst);
+                actix_rt::time::sleep(Duration::from_millis(50)).await;
+            }).await.unwrap();
+        };
+        future::join(tick(), tick()).await;
+
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        // The guard is released once the run finished
+        tick().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,12 +1,19 @@
 use crate::config::mail_config::MailClient;
-use crate::models::doctor_appointment::AppointmentPicking;
+use crate::error::AppError;
+use crate::models::doctor_appointment::{AppointmentChange, AppointmentPicking, ChangeType};
+use crate::services::digest_service::DoctorDigest;
+use crate::services::mail_template::{self, MailTemplates};
+use crate::services::notifier::{Notification, Notifier, RunMetadata};
+use async_trait::async_trait;
 use lettre::message::{Mailbox, MultiPart, SinglePart};
-use lettre::transport::smtp::authentication::Credentials;
 use lettre::{Message, SmtpTransport, Transport};
+use minijinja::{context, Value};
+use serde::Serialize;
 
 #[derive(Debug, Clone)]
 pub struct MailService {
     mail_client: MailClient,
+    templates: MailTemplates,
 }
 
 impl MailService {
@@ -14,151 +21,57 @@ impl MailService {
         MailServiceBuilder::new(mail_client)
     }
 
-    pub fn send_email(&self, appointment: &AppointmentPicking) -> Result<(), &str> {
-        let doctor_name = appointment.doctor_name.clone().unwrap_or("Unknown".to_string());
-
-        let slots = appointment.available_slot.as_ref().map(|slots| {
-            slots.iter().map(|slot| {
-                format!(
-                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
-                    slot.start_time.clone(),
-                    slot.end_time.clone(),
-                    slot.max_slot.unwrap_or(0),
-                    slot.available_slot.unwrap_or(0)
-                )
-            }).collect::<Vec<String>>().join("")
-        }).unwrap_or_else(|| "<tr><td colspan='4'>No available slots</td></tr>".to_string());
-
-
-        let html_content = format!(
-            r#"
-        <!DOCTYPE html>
-        <html lang="en">
-        <head>
-            <meta charset="UTF-8">
-            <meta name="viewport" content="width=device-width, initial-scale=1.0">
-            <style>
-                body {{
-                    font-family: Arial, sans-serif;
-                    background-color: #f4f4f4;
-                    margin: 0;
-                    padding: 20px;
-                }}
-                .container {{
-                    background-color: #ffffff;
-                    padding: 20px;
-                    border-radius: 8px;
-                    box-shadow: 0 0 10px rgba(0, 0, 0, 0.1);
-                    max-width: 600px;
-                    margin: 0 auto;
-                }}
-                h2 {{
-                    color: #4CAF50;
-                    text-align: center;
-                }}
-                .doctor-info {{
-                    margin-bottom: 20px;
-                }}
-                .doctor-info p {{
-                    font-size: 16px;
-                    color: #333;
-                }}
-                table {{
-                    width: 100%;
-                    border-collapse: collapse;
-                    margin-bottom: 20px;
-                }}
-                table, th, td {{
-                    border: 1px solid #ddd;
-                }}
-                th, td {{
-                    padding: 12px;
-                    text-align: center;
-                }}
-                th {{
-                    background-color: #4CAF50;
-                    color: white;
-                }}
-                td {{
-                    color: #333;
-                }}
-                .footer {{
-                    text-align: center;
-                    margin-top: 20px;
-                    color: #888;
-                    font-size: 12px;
-                }}
-            </style>
-        </head>
-        <body>
-
-        <div class="container">
-            <h2>Appointment Notification</h2>
-
-            <div class="doctor-info">
-                <p><strong>Doctor Name:</strong> {doctor_name}</p>
-                <p><strong>Appointment Date:</strong> {appointment_date}</p>
-                <p><strong>Appointment Day:</strong> {appointment_day}</p>
-            </div>
-
-            <table>
-                <thead>
-                    <tr>
-                        <th>Start Time</th>
-                        <th>End Time</th>
-                        <th>Max Slots</th>
-                        <th>Available Slots</th>
-                    </tr>
-                </thead>
-                <tbody>
-                    {slots}
-                </tbody>
-            </table>
-
-            <p>Please contact us if you need further assistance.</p>
-
-            <div class="footer">
-                <p>&copy; 2024 Medical Bot, All Rights Reserved</p>
-            </div>
-        </div>
-
-        </body>
-        </html>
-        "#,
-            doctor_name = doctor_name,
-            appointment_day = appointment.appointment_day.clone().unwrap_or(String::new()),
-            appointment_date = appointment.appointment_date.clone().unwrap(),
-            slots = slots
-        );
+    pub fn send_email(&self, notification: &Notification) -> Result<(), AppError> {
+        let ctx = alert_context(notification);
+        let subject = self.templates.render_subject(mail_template::ALERT_SUBJECT, &ctx)?;
+        let html_content = self.templates.render(mail_template::ALERT_HTML, &ctx)?;
+        let text_content = self.templates.render(mail_template::ALERT_TEXT, &ctx)?;
+
+        self.send(&self.mail_client.target_email, subject, html_content, text_content)
+    }
+
+    /// One email for all digest items, grouped by doctor then appointment date
+    pub fn send_digest(&self, recipients: &str, doctors: &[DoctorDigest]) -> Result<(), AppError> {
+        let ctx = digest_context(doctors, RunMetadata::now());
+        let subject = self.templates.render_subject(mail_template::DIGEST_SUBJECT, &ctx)?;
+        let html_content = self.templates.render(mail_template::DIGEST_HTML, &ctx)?;
+        let text_content = self.templates.render(mail_template::DIGEST_TEXT, &ctx)?;
+
+        self.send(recipients, subject, html_content, text_content)
+    }
 
+    /// Sends `text_content` and `html_content` as alternatives, clients without HTML show the text
+    fn send(&self, recipients: &str, subject: String, html_content: String, text_content: String) -> Result<(), AppError> {
         log::info!("Sending email from: {}", self.mail_client.from_email.clone());
 
         let from_email = format!(r#"MED bot <{}>"#,
-                                 self.mail_client.from_email.to_string())
-            .parse::<Mailbox>().unwrap_or_else(|err| {
-            panic!("Failed to parse email into Mailbox: {:?}", err);
-        });
+                                 self.mail_client.from_email)
+            .parse::<Mailbox>()
+            .map_err(|err| AppError::Mail(format!("Failed to parse email into Mailbox: {:?}", err)))?;
 
         let mut email_builder = Message::builder()
             .from(from_email)
-            .subject("Appointment Event");
+            .subject(subject);
 
-        let target_emails = self.mail_client.target_email
+        let target_emails = recipients
             .split(";")
             .collect::<Vec<&str>>();
 
         for recipient in target_emails {
-            email_builder = email_builder.to(recipient.parse::<Mailbox>().unwrap());
+            email_builder = email_builder.to(recipient.parse::<Mailbox>()
+                .map_err(|err| AppError::Mail(format!("Invalid recipient {}: {:?}", recipient, err)))?);
         }
 
         let email_builder_content = email_builder.multipart(
-            MultiPart::alternative().singlepart(SinglePart::html(html_content.to_string())),
-        ).unwrap();
+            MultiPart::alternative()
+                .singlepart(SinglePart::plain(text_content))
+                .singlepart(SinglePart::html(html_content)),
+        ).map_err(|err| AppError::Mail(err.to_string()))?;
 
 
         // Open a secure connection to the SMTP server using STARTTLS
         let mailer = SmtpTransport::starttls_relay(self.mail_client.smtp_host.clone().as_str())
-            .unwrap()  // Unwrap the Result, panics in case of error
+            .map_err(|err| AppError::Mail(err.to_string()))?
             .credentials(self.mail_client.credentials.clone())
             .build();
 
@@ -172,12 +85,79 @@ impl MailService {
             // If there was an error sending the email, print the error
             Err(e) => {
                 log::error!("Could not send email: {:?}", e);
-                Err("Could not send email")
+                Err(AppError::Mail(e.to_string()))
             }
         }
     }
 }
 
+fn alert_context(notification: &Notification) -> Value {
+    let mut change_types = notification.changes.iter()
+        .map(|change| change.change_type)
+        .collect::<Vec<ChangeType>>();
+    change_types.sort();
+    change_types.dedup();
+
+    context! {
+        target => &notification.target,
+        change_types => change_types.iter().map(|change_type| change_type.label()).collect::<Vec<&str>>(),
+        changes => notification.changes.iter().map(ChangeContext::from).collect::<Vec<ChangeContext>>(),
+        run => &notification.run,
+    }
+}
+
+fn digest_context(doctors: &[DoctorDigest], run: RunMetadata) -> Value {
+    let total_changes = doctors.iter()
+        .flat_map(|doctor| doctor.dates.iter())
+        .map(|(_, changes)| changes.len())
+        .sum::<usize>();
+
+    context! {
+        doctors => doctors.iter().map(|doctor| context! {
+            doctor_name => &doctor.doctor_name,
+            dates => doctor.dates.iter().map(|(date, changes)| context! {
+                date => date,
+                changes => changes.iter().map(ChangeContext::from).collect::<Vec<ChangeContext>>(),
+            }).collect::<Vec<Value>>(),
+        }).collect::<Vec<Value>>(),
+        total_changes => total_changes,
+        run => run,
+    }
+}
+
+/// A change as seen by the templates, with its display label
+#[derive(Serialize)]
+struct ChangeContext<'a> {
+    change_type: ChangeType,
+    label: &'static str,
+    appointment: &'a AppointmentPicking,
+}
+
+impl<'a> From<&'a AppointmentChange> for ChangeContext<'a> {
+    fn from(change: &'a AppointmentChange) -> Self {
+        ChangeContext {
+            change_type: change.change_type,
+            label: change.change_type.label(),
+            appointment: &change.appointment,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for MailService {
+    fn name(&self) -> &str {
+        "mail"
+    }
+
+    fn recipient(&self) -> String {
+        self.mail_client.target_email.clone()
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), AppError> {
+        self.send_email(notification)
+    }
+}
+
 pub struct MailServiceBuilder {
     mail_client: MailClient,
 }
@@ -188,8 +168,68 @@ impl MailServiceBuilder {
     }
 
     pub fn build(self) -> MailService {
+        let templates = MailTemplates::load(self.mail_client.template_dir.as_deref());
         MailService {
-            mail_client: self.mail_client
+            mail_client: self.mail_client,
+            templates,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::notifier::test_support::sample_notification;
+
+    fn render_all(templates: &MailTemplates, names: [&str; 3], ctx: &Value) -> [String; 3] {
+        names.map(|name| templates.render(name, ctx)
+            .unwrap_or_else(|err| panic!("{} did not render: {}", name, err)))
+    }
+
+    #[test]
+    fn renders_the_built_in_alert_templates() {
+        let templates = MailTemplates::load(None);
+        let notification = sample_notification();
+        let run_id = notification.run.run_id.clone().unwrap();
+        let ctx = alert_context(&notification);
+
+        let subject = templates.render_subject(mail_template::ALERT_SUBJECT, &ctx).unwrap();
+        assert_eq!(subject, "Appointment Event: New slots opened - Nguyen Van A <ENT>");
+
+        let [_, html, text] = render_all(
+            &templates, [mail_template::ALERT_SUBJECT, mail_template::ALERT_HTML, mail_template::ALERT_TEXT], &ctx,
+        );
+        // The layout and the appointment table macro are rendered through the alert
+        assert!(html.contains("Nguyen Van A &lt;ENT&gt;"));
+        assert!(html.contains("<td>07:30</td><td>08:00</td><td>4</td><td>2</td>"));
+        assert!(html.contains(&format!("cron run {}", run_id)));
+        assert!(text.contains("Nguyen Van A <ENT>"));
+        assert!(text.contains("2024-10-02 Buoi sang"));
+        assert!(text.contains("  - 07:30 - 08:00: 2/4 available"));
+        assert!(text.contains(&format!("in cron run {}", run_id)));
+    }
+
+    #[test]
+    fn renders_the_built_in_digest_templates() {
+        let templates = MailTemplates::load(None);
+        let notification = sample_notification();
+        let doctors = vec![DoctorDigest {
+            doctor_name: notification.target.doctor_name.clone(),
+            dates: vec![("2024-10-02".to_string(), notification.changes.clone())],
+        }];
+        let ctx = digest_context(&doctors, RunMetadata::now());
+
+        let subject = templates.render_subject(mail_template::DIGEST_SUBJECT, &ctx).unwrap();
+        assert_eq!(subject, "Appointment Digest: 1 changes for 1 doctors");
+
+        let [_, html, text] = render_all(
+            &templates, [mail_template::DIGEST_SUBJECT, mail_template::DIGEST_HTML, mail_template::DIGEST_TEXT], &ctx,
+        );
+        assert!(html.contains("Appointment Digest"));
+        assert!(html.contains("Nguyen Van A &lt;ENT&gt;"));
+        assert!(html.contains("<td>07:30</td><td>08:00</td><td>4</td><td>2</td>"));
+        assert!(!html.contains(" run "));
+        assert!(text.contains("== Nguyen Van A <ENT> =="));
+        assert!(text.contains("    - 07:30 - 08:00: 2/4 available"));
+    }
+}
@@ -0,0 +1,76 @@
+use crate::error::AppError;
+use minijinja::Environment;
+use serde::Serialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+pub const ALERT_SUBJECT: &str = "alert_subject.txt.jinja";
+pub const ALERT_HTML: &str = "alert.html.jinja";
+pub const ALERT_TEXT: &str = "alert.txt.jinja";
+pub const DIGEST_SUBJECT: &str = "digest_subject.txt.jinja";
+pub const DIGEST_HTML: &str = "digest.html.jinja";
+pub const DIGEST_TEXT: &str = "digest.txt.jinja";
+
+/// Templates compiled into the binary, used unless overridden by `MAIL_TEMPLATE_DIR`
+const BUILT_IN_TEMPLATES: [(&str, &str); 8] = [
+    ("layout.html.jinja", include_str!("../templates/layout.html.jinja")),
+    ("appointment.html.jinja", include_str!("../templates/appointment.html.jinja")),
+    (ALERT_SUBJECT, include_str!("../templates/alert_subject.txt.jinja")),
+    (ALERT_HTML, include_str!("../templates/alert.html.jinja")),
+    (ALERT_TEXT, include_str!("../templates/alert.txt.jinja")),
+    (DIGEST_SUBJECT, include_str!("../templates/digest_subject.txt.jinja")),
+    (DIGEST_HTML, include_str!("../templates/digest.html.jinja")),
+    (DIGEST_TEXT, include_str!("../templates/digest.txt.jinja")),
+];
+
+/// Mail templates. HTML templates (`*.html.jinja`) are autoescaped, plaintext ones are not.
+#[derive(Debug, Clone)]
+pub struct MailTemplates {
+    env: Environment<'static>,
+}
+
+impl MailTemplates {
+    /// Loads the built-in templates, replacing each one by the file with the same name in
+    /// `template_dir` when there is one. An override that does not compile is logged and skipped.
+    pub fn load(template_dir: Option<&str>) -> MailTemplates {
+        let mut env = Environment::new();
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+
+        for (name, source) in BUILT_IN_TEMPLATES {
+            env.add_template(name, source)
+                .unwrap_or_else(|err| panic!("Built-in mail template {} is invalid: {}", name, err));
+
+            let Some(template_dir) = template_dir else { continue };
+            let path = Path::new(template_dir).join(name);
+            match fs::read_to_string(&path) {
+                Ok(custom) => match env.add_template_owned(name, custom) {
+                    Ok(_) => log::info!("Using mail template {}", path.display()),
+                    Err(err) => {
+                        log::error!("Invalid mail template {}, using the built-in one: {}", path.display(), err);
+                        env.add_template(name, source)
+                            .unwrap_or_else(|err| panic!("Built-in mail template {} is invalid: {}", name, err));
+                    }
+                },
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => log::error!("Could not read mail template {}: {}", path.display(), err),
+            }
+        }
+
+        MailTemplates { env }
+    }
+
+    pub fn render<S: Serialize>(&self, name: &str, ctx: S) -> Result<String, AppError> {
+        self.env
+            .get_template(name)
+            .and_then(|template| template.render(ctx))
+            .map_err(|err| AppError::Mail(format!("Failed to render {}: {:#}", name, err)))
+    }
+
+    /// Renders a subject template onto a single line
+    pub fn render_subject<S: Serialize>(&self, name: &str, ctx: S) -> Result<String, AppError> {
+        let subject = self.render(name, ctx)?;
+        Ok(subject.split_whitespace().collect::<Vec<&str>>().join(" "))
+    }
+}
@@ -1,175 +1,447 @@
 use crate::dto::appointment_model::{AppointmentApiResponse, Day, TimeSlot};
+use crate::dto::doctor_model::ResolveDoctorRequest;
 use crate::dto::search_model::{ResultItem, SearchApiResponse};
-use crate::models::doctor_appointment::{AppointmentPicking, DoctorAppointment};
-use crate::models::documents::Doctor;
-use crate::repositories::doctor_repository::{MongoDoctorRepository, MongoDoctorRepositoryBuilder};
-use crate::services::mail_service::MailService;
-use chrono::{NaiveDate, NaiveDateTime};
-use cron::TimeUnitSpec;
+use crate::models::doctor_appointment::{AppointmentChange, AppointmentPicking, AppointmentWindow, DoctorAppointment, TargetAnalysis};
+use crate::models::documents::{Doctor, RunContext};
+use crate::cache::TtlCache;
+use crate::metrics::Metrics;
+use crate::resilience::{CircuitStatus, UpstreamGuard};
+use crate::repositories::doctor_repository::MongoDoctorRepository;
+use crate::services::booking_provider::BookingProvider;
+use crate::services::notifier::{Notification, NotificationTarget, Notifier, RunMetadata};
+use crate::services::ledger_service::LedgerService;
+use crate::services::observation_service::ObservationService;
+use crate::services::snapshot_service::SnapshotService;
+use chrono::Local;
+use futures::future::join_all;
+use futures::stream::{self, StreamExt};
 use mongodb::Collection;
-use reqwest::{Client, Error};
-use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use crate::config::med_target_config::MedTarget;
+use crate::error::AppError;
+
+/// Request parameters a search response is cached under
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SearchKey {
+    search_key: String,
+    city_id: String,
+    subject_id: String,
+    /// Resolved header profile, the site may answer differently per platform
+    header_profile: String,
+}
+
+/// Request parameters an appointment schedule is cached under
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct AppointmentKey {
+    subject_id: String,
+    doctor_id: String,
+    service_id: String,
+    partner_id: String,
+    tree_id: Option<String>,
+    header_profile: String,
+}
+
+/// An upstream response, and whether this call fetched it rather than reading it from the cache
+#[derive(Debug)]
+struct Fetched<V> {
+    response: V,
+    from_upstream: bool,
+}
 
 pub struct MedService {
     med_target: MedTarget,
+    booking_provider: Arc<dyn BookingProvider>,
+    upstream_guard: UpstreamGuard,
+    metrics: Arc<Metrics>,
+    search_cache: TtlCache<SearchKey, Vec<SearchApiResponse>>,
+    appointment_cache: TtlCache<AppointmentKey, AppointmentApiResponse>,
     mongo_doctor_repository: MongoDoctorRepository,
-    mail_service: MailService,
+    snapshot_service: SnapshotService,
+    observation_service: ObservationService,
+    ledger_service: LedgerService,
+    notifiers: Vec<Arc<dyn Notifier>>,
 }
 
 impl MedService {}
 
 impl MedService {
-    pub fn builder(med_target: MedTarget, collection: Collection<Doctor>, mail_service: MailService) -> MedServiceBuilder {
-        MedServiceBuilder::new(med_target, collection, mail_service)
-    }
-
-    pub async fn search_med(&self, client: &Client, search_key: String, city_id: String, subject_id: String) -> Result<Vec<SearchApiResponse>, Box<Error>> {
-        let mut map = HashMap::new();
-        map.insert("search_key", search_key);
-        map.insert("category", String::from("doctor"));
-        map.insert("city_id", city_id);
-        map.insert("limit", String::from("3"));
-        map.insert("offset", String::from("1"));
-        map.insert("subject_ids", subject_id);
-
-        let result = client.post(self.med_target.search_med_api.clone())
-            .header("User-Agent", "Mozilla/5.0 (X11; Linux x86_64; rv:130.0) Gecko/20100101 Firefox/130.0")
-            .header("Accept", "application/json, text/plain, */*")
-            .header("Accept-Language", "en-US,en;q=0.5")
-            .header("Accept-Encoding", "gzip, deflate, br, zstd")
-            .header("Content-Type", "application/json;charset=utf-8")
-            .header("locale", "vi")
-            .header("platform", "web")
-            .header("Origin", self.med_target.origin_header.clone())
-            .header("Connection", "keep-alive")
-            .header("Referer", self.med_target.origin_header.clone())
-            .header("Sec-Fetch-Dest", "empty")
-            .header("Sec-Fetch-Mode", "cors")
-            .header("Sec-Fetch-Site", "cross-site")
-            .json(&map)
-            .send()
-            .await?;
+    pub fn builder(med_target: MedTarget, collection: Collection<Doctor>) -> MedServiceBuilder {
+        MedServiceBuilder::new(med_target, collection)
+    }
 
-        // Extract the response body as text
-        let raw_json = result.text().await?;
-        // println!("Raw JSON response: {}", raw_json);
-
-        // Deserialize the JSON
-        let deserialized_result: Vec<SearchApiResponse> = serde_json::from_str(&raw_json).unwrap();
-
-        // Return the deserialized result
-        Ok(deserialized_result)
-    }
-
-    pub async fn get_appointments(&self, client: &Client, subject_id: String, doctor_id: String, service_id: String, partner_id: String) -> Result<AppointmentApiResponse, Box<Error>> {
-        let mut map = HashMap::new();
-        map.insert("subjectId", subject_id);
-        map.insert("doctorId", doctor_id);
-        map.insert("serviceId", service_id);
-        map.insert("treeId", "DATE".parse().unwrap());
-
-        let result = client.post(self.med_target.appointment_api.clone())
-            .header("User-Agent", "Mozilla/5.0 (X11; Linux x86_64; rv:130.0) Gecko/20100101 Firefox/130.0")
-            .header("Accept", "application/json, text/plain, */*")
-            .header("Accept-Language", "en-US,en;q=0.5")
-            .header("Accept-Encoding", "gzip, deflate, br, zstd")
-            .header("Content-Type", "application/json;charset=utf-8")
-            .header("partnerid", partner_id)
-            .header("appid", self.med_target.appid_header.clone())
-            .header("locale", "vi")
-            .header("platform", "pc")
-            .header("Origin", self.med_target.origin_header.clone())
-            .header("Connection", "keep-alive")
-            .header("Referer", self.med_target.origin_header.clone())
-            .header("Sec-Fetch-Dest", "empty")
-            .header("Sec-Fetch-Mode", "cors")
-            .header("Sec-Fetch-Site", "cross-site")
-            .json(&map)
-            .send()
-            .await?;
+    /// Doctor search, reused from the cache for `cache_ttl` unless `no_cache` is set
+    pub async fn search_med(&self, search_key: String, city_id: String, subject_id: String, header_profile: Option<&str>, no_cache: bool) -> Result<Vec<SearchApiResponse>, AppError> {
+        let key = SearchKey {
+            search_key: search_key.clone(),
+            city_id: city_id.clone(),
+            subject_id: subject_id.clone(),
+            header_profile: self.header_profile_name(header_profile),
+        };
+
+        let fetched = self.cached_or_fetch("search_med", &self.search_cache, key, no_cache, || {
+            self.upstream_guard.call("search_med", || self.booking_provider.search_med(
+                search_key.clone(),
+                city_id.clone(),
+                subject_id.clone(),
+                header_profile,
+            ))
+        }).await?;
+        Ok(fetched.response)
+    }
+
+    /// Appointment schedule, reused from the cache for `cache_ttl` unless `no_cache` is set
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_appointments(&self, subject_id: String, doctor_id: String, service_id: String, partner_id: String, tree_id: Option<String>, header_profile: Option<&str>, no_cache: bool) -> Result<AppointmentApiResponse, AppError> {
+        let fetched = self.fetch_appointments(subject_id, doctor_id, service_id, partner_id, tree_id, header_profile, no_cache).await?;
+        Ok(fetched.response)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch_appointments(&self, subject_id: String, doctor_id: String, service_id: String, partner_id: String, tree_id: Option<String>, header_profile: Option<&str>, no_cache: bool) -> Result<Fetched<AppointmentApiResponse>, AppError> {
+        let key = AppointmentKey {
+            subject_id: subject_id.clone(),
+            doctor_id: doctor_id.clone(),
+            service_id: service_id.clone(),
+            partner_id: partner_id.clone(),
+            tree_id: tree_id.clone(),
+            header_profile: self.header_profile_name(header_profile),
+        };
+
+        self.cached_or_fetch("appointments", &self.appointment_cache, key, no_cache, || {
+            self.upstream_guard.call("appointments", || self.booking_provider.get_appointments(
+                subject_id.clone(),
+                doctor_id.clone(),
+                service_id.clone(),
+                partner_id.clone(),
+                tree_id.clone(),
+                header_profile,
+            ))
+        }).await
+    }
+
+    fn header_profile_name(&self, header_profile: Option<&str>) -> String {
+        self.med_target.header_profiles.resolve(header_profile).0.to_string()
+    }
+
+    /// Serves `key` from the cache, or fetches and stores it. Concurrent misses on the same key
+    /// wait for the first fetch and share its response instead of calling upstream again.
+    async fn cached_or_fetch<K, V, F, Fut>(&self, endpoint: &str, cache: &TtlCache<K, V>, key: K, no_cache: bool, fetch: F) -> Result<Fetched<V>, AppError>
+    where
+        K: Eq + Hash + Clone,
+        V: Clone,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, AppError>>,
+    {
+        if no_cache || !cache.is_enabled() {
+            return fetch().await.map(|response| Fetched { response, from_upstream: true });
+        }
+        if let Some(response) = self.cached(endpoint, cache, &key) {
+            return Ok(Fetched { response, from_upstream: false });
+        }
 
-        // Extract the response body as text
-        let raw_json = result.text().await?;
-        // println!("Raw JSON response: {}", raw_json);
-
-        // Deserialize the JSON
-        let deserialized_result: AppointmentApiResponse = serde_json::from_str(&raw_json).unwrap();
-
-        // Return the deserialized result
-        Ok(deserialized_result)
-    }
-
-    pub async fn analyze_appointment(&self, client: &Client) -> Result<AppointmentPicking, Box<dyn std::error::Error>> {
-        let doctor_detail = self.mongo_doctor_repository
-            .get_target_doctor().await?;
-
-        if let Some(doctor) = doctor_detail {
-            log::info!("Got doctor");
-            let search_response = self.search_med(
-                client,
-                doctor.doctor_name.to_owned(),
-                doctor.city_id.to_owned(),
-                doctor.subject_ref_id.to_owned(),
-            ).await?;
-            log::info!("Got search response");
-            if let Some(first_med) = search_response.first() {
-                if let Some(first_doctor_item) = first_med.results.first() {
-                    let mut analyze_doctor = DoctorAppointment::default();
-
-                    // Validate doctor details
-                    if !self.validate_doctor(first_doctor_item, &mut analyze_doctor, &doctor) {
-                        return Err(Box::<dyn std::error::Error>::from("Invalid doctor"));
-                    }
-
-                    // Fetch doctor appointments
-                    let doctor_appointment_result = self.get_appointments(
-                        client,
-                        analyze_doctor.subject_id.as_deref().unwrap().to_string(),
-                        analyze_doctor.doctor_id.as_deref().unwrap().to_string(),
-                        analyze_doctor.service_id.as_deref().unwrap().to_string(),
-                        analyze_doctor.partner_id.as_deref().unwrap().to_string(),
-                    ).await?;
-                    log::info!("Got appointments");
-
-                    // Process appointment and find available slot
-                    let checked_appointments = doctor_appointment_result.days.iter()
-                        .find_map(|appointment| {
-                            log::info!("Checking appointment: {:?}", appointment);
-                            self.find_available_shift(
-                                appointment,
-                                doctor.doctor_name.clone(),
-                                doctor.target_date.clone(),
-                            )
-                        });
-
-                    if let Some(checked_appointments) = checked_appointments {
-                        self.mail_service.send_email(&checked_appointments)?;
-                        return Ok(checked_appointments)
-                    } else {
-                        let result_appointment = AppointmentPicking {
-                            doctor_name: Some(doctor.doctor_name.clone()),
-                            appointment_day: None,
-                            appointment_date: Some(doctor.target_date.clone()),
-                            available_slot: None,
-                            doctor_change_info: None,
-                        };
-
-                        self.mail_service.send_email(&result_appointment)?;
-                        return Ok(result_appointment);
-                    }
+        let flight = cache.flight(&key);
+        let _flight_guard = flight.lock().await;
+        if let Some(response) = cache.get(&key) {
+            log::info!("Serving {} fetched by a concurrent request", endpoint);
+            return Ok(Fetched { response, from_upstream: false });
+        }
+
+        let response = fetch().await?;
+        cache.insert(key, response.clone());
+        Ok(Fetched { response, from_upstream: true })
+    }
+
+    /// Cache lookup counted in the hit/miss metrics
+    fn cached<K: Eq + Hash + Clone, V: Clone>(&self, endpoint: &str, cache: &TtlCache<K, V>, key: &K) -> Option<V> {
+        let response = cache.get(key);
+        self.metrics.record_cache_lookup(endpoint, response.is_some());
+        if response.is_some() {
+            log::info!("Serving {} from cache", endpoint);
+        }
+        response
+    }
+
+    pub fn circuit_status(&self) -> CircuitStatus {
+        self.upstream_guard.circuit_status()
+    }
+
+    pub async fn get_target_doctors(&self) -> Result<Vec<Doctor>, AppError> {
+        Ok(self.mongo_doctor_repository.get_target_doctors().await?)
+    }
+
+    pub async fn analyze_appointment(&self, run: RunContext, no_cache: bool) -> Result<Vec<TargetAnalysis>, AppError> {
+        let doctors = self.get_target_doctors().await?;
+        self.analyze_doctors(doctors, run, no_cache).await
+    }
+
+    pub async fn analyze_doctors(&self, doctors: Vec<Doctor>, run: RunContext, no_cache: bool) -> Result<Vec<TargetAnalysis>, AppError> {
+        if doctors.is_empty() {
+            return Err(AppError::NoTarget);
+        }
+
+        log::info!("Analyzing {} target doctors", doctors.len());
+
+        // Check every target, keeping at most `analyze_concurrency` upstream lookups in flight
+        let results = stream::iter(doctors)
+            .map(|doctor| async move {
+                let mut analysis = TargetAnalysis {
+                    target_id: doctor.id,
+                    doctor_ref_id: doctor.doctor_ref_id.clone(),
+                    doctor_name: doctor.doctor_name.clone(),
+                    ..TargetAnalysis::default()
+                };
+                if let Err(e) = self.analyze_doctor(&doctor, &mut analysis, &run, no_cache).await {
+                    log::error!("Analyze doctor {} failed: {}", doctor.doctor_name, e);
+                    analysis.error = Some(e.to_string());
+                    analysis.error_code = Some(e.code().to_string());
                 }
+                analysis
+            })
+            .buffered(self.med_target.analyze_concurrency)
+            .collect::<Vec<TargetAnalysis>>()
+            .await;
+
+        Ok(results)
+    }
+
+    /// Fills `analysis` as the check progresses, so a failing step still leaves what was seen before it
+    async fn analyze_doctor(&self, doctor: &Doctor, analysis: &mut TargetAnalysis, run: &RunContext, no_cache: bool) -> Result<(), AppError> {
+        log::info!("Got doctor {}", doctor.doctor_name);
+        let window = AppointmentWindow::from_doctor(doctor, Local::now().date_naive())
+            .map_err(AppError::DoctorValidation)?;
+
+        let schedule = self.fetch_schedule(doctor, analysis, no_cache).await?;
+        log::info!("Got appointments");
+
+        // Availability history is best effort, it never blocks the alerts. A cached schedule is
+        // not a new observation, so only schedules fetched for this run are recorded.
+        if schedule.from_upstream {
+            if let Err(e) = self.observation_service
+                .record_observations(doctor, &schedule.response.days)
+                .await {
+                log::error!("Failed to record slot observations for {}: {}", doctor.doctor_name, e);
             }
         }
+        let schedule = schedule.response;
 
-        Err("Analyze appointment fail".into())
-    }
+        analysis.appointments = self.match_appointments(doctor, &schedule.days, &window);
 
-    pub async fn get_doctor(&self) -> Result<Doctor, Box<dyn std::error::Error>> {
-        let doctor_detail = self.mongo_doctor_repository
-            .get_doctor_by_doctor_ref_id(String::from("test_ref_id"))
+        // Notify only about slots that opened, changed or disappeared since the previous run
+        analysis.changes = self.snapshot_service
+            .diff_with_previous(doctor, &analysis.appointments)
             .await?;
-        Ok(doctor_detail.unwrap())
+
+        if analysis.changes.is_empty() {
+            log::info!("No availability change for {} in {}", doctor.doctor_name, window.label());
+        } else {
+            let target = NotificationTarget::new(doctor, &window);
+            let run = RunMetadata::for_run(run);
+            analysis.notifications_sent = self.notify_all(doctor, &target, &run, &analysis.changes).await?;
+        }
+
+        // Store the snapshot only once every channel delivered, so a failed channel is retried next run
+        self.snapshot_service
+            .save_snapshot(doctor, &analysis.appointments)
+            .await
+    }
+
+    /// Searches the doctor, checks the first hit is the target and fetches its appointment schedule,
+    /// adding the time spent upstream to `analysis`
+    async fn fetch_schedule(&self, doctor: &Doctor, analysis: &mut TargetAnalysis, no_cache: bool) -> Result<Fetched<AppointmentApiResponse>, AppError> {
+        let upstream_started_at = Instant::now();
+        let search_response = self.search_med(
+            doctor.doctor_name.to_owned(),
+            doctor.city_id.to_owned(),
+            doctor.subject_ref_id.to_owned(),
+            doctor.header_profile.as_deref(),
+            no_cache,
+        ).await;
+        analysis.upstream_latency_ms = upstream_started_at.elapsed().as_millis() as u64;
+        let search_response = search_response?;
+        log::info!("Got search response");
+
+        let Some(first_doctor_item) = search_response.first().and_then(|first_med| first_med.results.first()) else {
+            return Err(AppError::NotFound(format!("Search result for doctor {}", doctor.doctor_name)));
+        };
+        let mut analyze_doctor = DoctorAppointment::default();
+
+        // Validate doctor details
+        if !self.validate_doctor(first_doctor_item, &mut analyze_doctor, doctor) {
+            return Err(AppError::DoctorValidation(format!(
+                "Search result does not match doctor {}", doctor.doctor_name
+            )));
+        }
+
+        // Fetch doctor appointments
+        let (Some(subject_id), Some(doctor_id), Some(service_id), Some(partner_id)) = (
+            analyze_doctor.subject_id,
+            analyze_doctor.doctor_id,
+            analyze_doctor.service_id,
+            analyze_doctor.partner_id,
+        ) else {
+            return Err(AppError::DoctorValidation(format!(
+                "Search result for doctor {} is missing subject, doctor, service or partner id", doctor.doctor_name
+            )));
+        };
+        let upstream_started_at = Instant::now();
+        let schedule = self.fetch_appointments(
+            subject_id,
+            doctor_id,
+            service_id,
+            partner_id,
+            None,
+            doctor.header_profile.as_deref(),
+            no_cache,
+        ).await;
+        analysis.upstream_latency_ms += upstream_started_at.elapsed().as_millis() as u64;
+        schedule
+    }
+
+    /// Every day and shift of the schedule with slots matching the target window and preferences
+    fn match_appointments(&self, doctor: &Doctor, days: &[Day], window: &AppointmentWindow) -> Vec<AppointmentPicking> {
+        days.iter()
+            .flat_map(|appointment| {
+                log::info!("Checking appointment: {:?}", appointment);
+                self.find_available_shifts(
+                    appointment,
+                    doctor.doctor_name.clone(),
+                    window,
+                )
+            })
+            .collect()
+    }
+
+    /// Fans the changes out to every channel, failing when any channel with something to send
+    /// did not deliver it. Delivered channels are in the ledger, so the retry only reaches the others.
+    async fn notify_all(&self, doctor: &Doctor, target: &NotificationTarget, run: &RunMetadata, changes: &[AppointmentChange]) -> Result<usize, AppError> {
+        let results = join_all(self.notifiers.iter().map(|notifier| async move {
+            let result = self.notify_channel(notifier.as_ref(), doctor, target, run, changes).await;
+            if let Err(e) = &result {
+                log::error!("Notifier {} failed for {}: {}", notifier.name(), doctor.doctor_name, e);
+            }
+            (notifier.name(), result)
+        })).await;
+
+        let delivered = results.iter().filter(|(_, result)| matches!(result, Ok(true))).count();
+        let failed = results.iter()
+            .filter(|(_, result)| result.is_err())
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>();
+        if !failed.is_empty() {
+            return Err(AppError::Notifier(format!(
+                "{} failed, delivered on {} channel(s)", failed.join(", "), delivered
+            )));
+        }
+
+        Ok(delivered)
+    }
+
+    /// Sends the changes the ledger doesn't hold back on this channel; `false` when nothing was left to send
+    async fn notify_channel(&self, notifier: &dyn Notifier, doctor: &Doctor, target: &NotificationTarget, run: &RunMetadata, changes: &[AppointmentChange]) -> Result<bool, AppError> {
+        let changes = self.ledger_service.hold_back(doctor, notifier.name(), changes).await?;
+        if changes.is_empty() {
+            log::info!("Changes for {} were already sent on {} within the cooldown", doctor.doctor_name, notifier.name());
+            return Ok(false);
+        }
+
+        let notification = Notification {
+            doctor_ref_id: doctor.doctor_ref_id.clone(),
+            doctor_name: doctor.doctor_name.clone(),
+            target: target.clone(),
+            changes,
+            run: run.clone(),
+        };
+        notifier.notify(&notification).await?;
+
+        // Already delivered, so a ledger failure only risks a repeated alert
+        if let Err(e) = self.ledger_service
+            .record(doctor, notifier.name(), &notifier.recipient(), &notification.changes)
+            .await {
+            log::error!("Failed to record {} notification for {} in the ledger: {}", notifier.name(), doctor.doctor_name, e);
+        }
+        Ok(true)
+    }
+
+    /// Searches the doctor and turns every hit passing `validate_doctor` into a ready-to-save target
+    pub async fn resolve_doctor(&self, request: &ResolveDoctorRequest) -> Result<Vec<Doctor>, AppError> {
+        let search_response = self.search_med(
+            request.doctor_name.to_owned(),
+            request.city_id.to_owned(),
+            request.subject_id.to_owned(),
+            request.target.as_ref().and_then(|target| target.header_profile.as_deref()),
+            false,
+        ).await?;
+
+        let candidates = search_response.iter()
+            .flat_map(|med| med.results.iter())
+            .flat_map(|doctor_item| self.doctor_candidates(doctor_item, request))
+            .collect::<Vec<Doctor>>();
+
+        log::info!("Resolved {} candidates for {}", candidates.len(), request.doctor_name);
+        Ok(candidates)
+    }
+
+    fn doctor_candidates(&self, doctor_item: &ResultItem, request: &ResolveDoctorRequest) -> Vec<Doctor> {
+        let (Some(doctor_ref_id), Some(doctor_name)) = (&doctor_item.id, &doctor_item.title) else {
+            return vec![];
+        };
+        let Some(hospital_id) = doctor_item.partner.as_ref().and_then(|partner| partner.partner_id.clone()) else {
+            return vec![];
+        };
+        if request.hospital_id.as_ref().is_some_and(|target_hospital| *target_hospital != hospital_id) {
+            return vec![];
+        }
+
+        let subject_filter = request.subject_name.as_ref().map(|name| name.to_lowercase());
+        let service_filter = request.service_name.as_ref().map(|name| name.to_lowercase());
+
+        // Subject and service names are stored lowercased, the way validate_doctor compares them
+        let mut candidates = vec![];
+        for subject_name in doctor_item.subjects.iter().flatten()
+            .filter_map(|subject| subject.name.as_ref().map(|name| name.to_lowercase()))
+            .filter(|name| subject_filter.as_ref().is_none_or(|filter| name.contains(filter.as_str()))) {
+            for service_name in doctor_item.services.iter().flatten()
+                .filter(|service| service.subject_names.as_ref().is_some_and(|names| {
+                    names.iter().any(|name| name.to_lowercase().contains(subject_name.as_str()))
+                }))
+                .filter_map(|service| service.name.as_ref().map(|name| name.to_lowercase()))
+                .filter(|name| service_filter.as_ref().is_none_or(|filter| name == filter)) {
+                let mut candidate = Doctor {
+                    id: None,
+                    doctor_ref_id: doctor_ref_id.clone(),
+                    doctor_name: doctor_name.clone(),
+                    subject_ref_id: request.subject_id.clone(),
+                    subject_name: subject_name.clone(),
+                    service_name,
+                    hospital_id: hospital_id.clone(),
+                    city_id: request.city_id.clone(),
+                    target_date: None,
+                    date_from: None,
+                    date_to: None,
+                    window_days: None,
+                    weekdays: None,
+                    earliest_time: None,
+                    latest_time: None,
+                    min_available_slot: None,
+                    poll_cron: None,
+                    header_profile: None,
+                    current_target: false,
+                    active: true,
+                };
+                if let Some(target) = &request.target {
+                    target.apply(&mut candidate);
+                }
+
+                if self.validate_doctor(doctor_item, &mut DoctorAppointment::default(), &candidate) {
+                    candidates.push(candidate);
+                }
+            }
+        }
+
+        candidates
     }
 
     fn validate_doctor(&self, doctor: &ResultItem, analyze_doctor: &mut DoctorAppointment, doctor_detail: &Doctor) -> bool {
@@ -179,7 +451,7 @@ impl MedService {
         // Check subject
         if let Some(subjects) = &doctor.subjects {
             if let Some(target_subject) = subjects.iter().find(|subject| {
-                subject.name.as_ref().map_or(false, |name| name.to_lowercase().contains(doctor_detail.subject_name.as_str()))
+                subject.name.as_ref().is_some_and(|name| name.to_lowercase().contains(doctor_detail.subject_name.as_str()))
             }) {
                 analyze_doctor.subject_id = Some(target_subject.id.clone());
             } else {
@@ -190,8 +462,8 @@ impl MedService {
         // Check service
         if let Some(services) = &doctor.services {
             if let Some(target_service) = services.iter().find(|service| {
-                service.name.as_ref().map_or(false, |name| name.to_lowercase() == doctor_detail.service_name.as_str())
-                    && service.subject_names.as_ref().map_or(false, |names| {
+                service.name.as_ref().is_some_and(|name| name.to_lowercase() == doctor_detail.service_name.as_str())
+                    && service.subject_names.as_ref().is_some_and(|names| {
                     names.iter().any(|name| name.to_lowercase().contains(doctor_detail.subject_name.as_str()))
                 })
             }) {
@@ -202,7 +474,7 @@ impl MedService {
         }
 
         // Check partner and city ID
-        let partner_valid = doctor.partner.as_ref().map_or(false, |partner| {
+        let partner_valid = doctor.partner.as_ref().is_some_and(|partner| {
             if partner.partner_id.as_deref() == Some(doctor_detail.hospital_id.as_str()) &&
                 partner.city_id.as_deref() == Some(doctor_detail.city_id.as_str()) {
                 analyze_doctor.partner_id = partner.partner_id.clone();
@@ -215,73 +487,334 @@ impl MedService {
         is_valid_doctor && partner_valid
     }
 
-    fn find_available_shift(&self, appointment: &Day, doctor_name: String, target_date: String) -> Option<AppointmentPicking> {
-        // Target date
-        let naive_target_date = NaiveDate::parse_from_str(target_date.as_str(), "%Y-%m-%d").unwrap();
-        log::info!("Checking for target date: {}", naive_target_date.clone());
-
-        let appointment_date = NaiveDateTime::from_timestamp_millis(appointment.date?).unwrap().date();
-        log::info!("Compare for appointment date: {}", appointment_date.clone());
-
-        if appointment_date == naive_target_date {
-            log::info!("Found available items for target date");
-            // Find a shift with available slots
-            appointment.shifts.iter().find_map(|shift| {
-                log::info!("Shift: {:?}", shift.shift_code);
-                let available_slots: Vec<TimeSlot> = shift.time_slot_in_day.as_ref()?.iter()
-                    .filter_map(|slot| {
-                        log::info!("Available Slot {}", slot.available_slot.clone().unwrap());
-                        if let (Some(available_slot), Some(max_slot)) = (slot.available_slot, slot.max_slot) {
-                            if available_slot > 0 && available_slot <= max_slot {
-                                return Some(TimeSlot {
-                                    available_slot: Some(available_slot),
-                                    max_slot: Some(max_slot),
-                                    // Add other fields from the original TimeSlot struct
-                                    ..slot.clone()  // Dereference `slot` and then clone it
-                                });
-                            }
-                        }
-                        None
-                    }).collect();
-
-                if !available_slots.is_empty() {
-                    return Some(AppointmentPicking {
-                        doctor_name: Some(doctor_name.clone()),
-                        appointment_date: Some(target_date.clone()),
-                        appointment_day: shift.days.clone(),
-                        available_slot: Some(available_slots),
-                        doctor_change_info: shift.doctor_change_info.clone(),
-                    });
-                }
-                None
-            })
-        } else {
-            None
+    fn find_available_shifts(&self, appointment: &Day, doctor_name: String, window: &AppointmentWindow) -> Vec<AppointmentPicking> {
+        let Some(appointment_date) = appointment.day_date() else {
+            log::warn!("Skipping appointment day without a valid date: {:?}", appointment.date.or(appointment.timemiliseconds));
+            return vec![];
+        };
+        log::info!("Compare for appointment date: {}", appointment_date);
+
+        if !window.contains_date(appointment_date) {
+            return vec![];
         }
+
+        log::info!("Found available items in target window {}", window.label());
+        // Keep every shift with slots matching the time-of-day and free place preferences
+        appointment.shifts.iter().filter_map(|shift| {
+            log::info!("Shift: {:?}", shift.shift_code);
+            let available_slots: Vec<TimeSlot> = shift.time_slot_in_day.as_ref()?.iter()
+                .filter(|slot| {
+                    log::info!("Available Slot {:?}", slot.available_slot);
+                    window.accepts_slot(slot)
+                })
+                .cloned()
+                .collect();
+
+            if !available_slots.is_empty() {
+                return Some(AppointmentPicking {
+                    doctor_name: Some(doctor_name.clone()),
+                    appointment_date: Some(appointment_date.format("%Y-%m-%d").to_string()),
+                    appointment_day: shift.days.clone(),
+                    shift_code: shift.shift_code.clone(),
+                    shift_name: shift.shift_name.clone(),
+                    available_slot: Some(available_slots),
+                    doctor_change_info: shift.doctor_change_info.clone(),
+                });
+            }
+            None
+        }).collect()
     }
 }
 
 pub struct MedServiceBuilder {
     med_target: MedTarget,
+    booking_provider: Option<Arc<dyn BookingProvider>>,
+    upstream_guard: Option<UpstreamGuard>,
+    metrics: Option<Arc<Metrics>>,
+    cache_ttl: Duration,
     mongo_doctor_repository: MongoDoctorRepository,
-    mail_service: MailService,
+    snapshot_service: Option<SnapshotService>,
+    observation_service: Option<ObservationService>,
+    ledger_service: Option<LedgerService>,
+    notifiers: Vec<Arc<dyn Notifier>>,
 }
 
 impl MedServiceBuilder {
-    pub fn new(med_target: MedTarget, collection: Collection<Doctor>, mail_service: MailService) -> MedServiceBuilder {
-        let mongo_doctor_repository = MongoDoctorRepositoryBuilder::new(collection).build();
+    pub fn new(med_target: MedTarget, collection: Collection<Doctor>) -> MedServiceBuilder {
+        let mongo_doctor_repository = MongoDoctorRepository::builder(collection).build();
         MedServiceBuilder {
             med_target,
+            booking_provider: None,
+            upstream_guard: None,
+            metrics: None,
+            cache_ttl: Duration::ZERO,
             mongo_doctor_repository,
-            mail_service,
+            snapshot_service: None,
+            observation_service: None,
+            ledger_service: None,
+            notifiers: vec![],
         }
     }
 
+    pub fn with_snapshot_service(mut self, snapshot_service: SnapshotService) -> MedServiceBuilder {
+        self.snapshot_service = Some(snapshot_service);
+        self
+    }
+
+    pub fn with_observation_service(mut self, observation_service: ObservationService) -> MedServiceBuilder {
+        self.observation_service = Some(observation_service);
+        self
+    }
+
+    pub fn with_ledger_service(mut self, ledger_service: LedgerService) -> MedServiceBuilder {
+        self.ledger_service = Some(ledger_service);
+        self
+    }
+
+    pub fn with_booking_provider(mut self, booking_provider: Arc<dyn BookingProvider>) -> MedServiceBuilder {
+        self.booking_provider = Some(booking_provider);
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> MedServiceBuilder {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Reuses upstream responses for `cache_ttl`, not cached by default
+    pub fn with_cache_ttl(mut self, cache_ttl: Duration) -> MedServiceBuilder {
+        self.cache_ttl = cache_ttl;
+        self
+    }
+
+    pub fn with_upstream_guard(mut self, upstream_guard: UpstreamGuard) -> MedServiceBuilder {
+        self.upstream_guard = Some(upstream_guard);
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> MedServiceBuilder {
+        self.notifiers.push(notifier);
+        self
+    }
+
     pub fn build(self) -> MedService {
         MedService {
             med_target: self.med_target,
+            booking_provider: self.booking_provider.expect("Booking provider not initialized"),
+            upstream_guard: self.upstream_guard.expect("Upstream guard not initialized"),
+            metrics: self.metrics.expect("Metrics not initialized"),
+            search_cache: TtlCache::new(self.cache_ttl),
+            appointment_cache: TtlCache::new(self.cache_ttl),
             mongo_doctor_repository: self.mongo_doctor_repository,
-            mail_service: self.mail_service,
+            snapshot_service: self.snapshot_service.expect("Snapshot service not initialized"),
+            observation_service: self.observation_service.expect("Observation service not initialized"),
+            ledger_service: self.ledger_service.expect("Ledger service not initialized"),
+            notifiers: self.notifiers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::header_profile_config::HeaderProfile;
+    use crate::config::retry_config::RetryConfig;
+    use crate::models::doctor_appointment::ChangeType;
+    use crate::services::mock_provider::MockBookingProvider;
+    use crate::services::snapshot_service::diff_appointments;
+    use chrono::NaiveDate;
+    use mongodb::Client;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// The fixture provider, counting the calls and slow enough for concurrent calls to overlap
+    struct CountingProvider {
+        fixtures: MockBookingProvider,
+        searches: AtomicUsize,
+        schedules: AtomicUsize,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl BookingProvider for CountingProvider {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        async fn search_med(&self, search_key: String, city_id: String, subject_id: String, header_profile: Option<&str>) -> Result<Vec<SearchApiResponse>, AppError> {
+            self.searches.fetch_add(1, Ordering::SeqCst);
+            actix_rt::time::sleep(Duration::from_millis(20)).await;
+            self.fixtures.search_med(search_key, city_id, subject_id, header_profile).await
+        }
+
+        async fn get_appointments(&self, subject_id: String, doctor_id: String, service_id: String, partner_id: String, tree_id: Option<String>, header_profile: Option<&str>) -> Result<AppointmentApiResponse, AppError> {
+            self.schedules.fetch_add(1, Ordering::SeqCst);
+            actix_rt::time::sleep(Duration::from_millis(20)).await;
+            self.fixtures.get_appointments(subject_id, doctor_id, service_id, partner_id, tree_id, header_profile).await
         }
     }
+
+    fn fixture_provider(metrics: &Arc<Metrics>) -> MockBookingProvider {
+        MockBookingProvider::builder(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures"))
+            .with_metrics(metrics.clone())
+            .build()
+    }
+
+    /// MedService over the bundled fixtures; the Mongo client is never connected
+    async fn mock_med_service() -> MedService {
+        let metrics = Arc::new(Metrics::default());
+        let booking_provider = Arc::new(fixture_provider(&metrics));
+        med_service(booking_provider, metrics, Duration::ZERO).await
+    }
+
+    async fn counting_med_service() -> (MedService, Arc<CountingProvider>) {
+        let metrics = Arc::new(Metrics::default());
+        let booking_provider = Arc::new(CountingProvider {
+            fixtures: fixture_provider(&metrics),
+            searches: AtomicUsize::new(0),
+            schedules: AtomicUsize::new(0),
+        });
+        let med_service = med_service(booking_provider.clone(), metrics, Duration::from_secs(60)).await;
+        (med_service, booking_provider)
+    }
+
+    async fn med_service(booking_provider: Arc<dyn BookingProvider>, metrics: Arc<Metrics>, cache_ttl: Duration) -> MedService {
+        let database = Client::with_uri_str("mongodb://localhost:27017").await.unwrap().database("med_bot_test");
+        let mut med_target = MedTarget::builder().build();
+        med_target.header_profiles.profiles.insert("mobile".to_string(), HeaderProfile::firefox());
+
+        MedService::builder(med_target, database.collection("doctor"))
+            .with_booking_provider(booking_provider)
+            .with_upstream_guard(UpstreamGuard::new(&RetryConfig::builder().build()))
+            .with_metrics(metrics)
+            .with_cache_ttl(cache_ttl)
+            .with_snapshot_service(SnapshotService::builder(database.collection("slot_snapshot")).build())
+            .with_observation_service(ObservationService::builder(database.collection("slot_observation")).build())
+            .with_ledger_service(LedgerService::builder(database.collection("notification_ledger"), Duration::ZERO).build())
+            .build()
+    }
+
+    fn fixture_doctor() -> Doctor {
+        serde_json::from_value(json!({
+            "doctor_ref_id": "doctor-001",
+            "doctor_name": "Nguyen Van A",
+            "subject_ref_id": "subject-001",
+            "subject_name": "tai mui hong",
+            "service_name": "kham dich vu",
+            "hospital_id": "partner-001",
+            "city_id": "city-hcm",
+            "date_from": "2024-10-01",
+            "date_to": "2024-10-14",
+            "current_target": true,
+            "active": true,
+        })).unwrap()
+    }
+
+    fn slot_ids(appointment: &AppointmentPicking) -> Vec<&str> {
+        appointment.available_slot.iter().flatten().map(|slot| slot.time_id.as_str()).collect()
+    }
+
+    #[actix_rt::test]
+    async fn matches_the_fixture_schedule() {
+        let med_service = mock_med_service().await;
+        let doctor = fixture_doctor();
+        let today = NaiveDate::from_ymd_opt(2024, 10, 1).unwrap();
+        let window = AppointmentWindow::from_doctor(&doctor, today).unwrap();
+
+        let mut analysis = TargetAnalysis::default();
+        let schedule = med_service.fetch_schedule(&doctor, &mut analysis, false).await.unwrap().response;
+        let appointments = med_service.match_appointments(&doctor, &schedule.days, &window);
+
+        // The full 08:00 slot is left out
+        assert_eq!(appointments.len(), 2);
+        assert_eq!(appointments[0].appointment_date.as_deref(), Some("2024-10-02"));
+        assert_eq!(appointments[0].shift_code.as_deref(), Some("MORNING"));
+        assert_eq!(slot_ids(&appointments[0]), ["slot-001"]);
+        assert_eq!(appointments[1].appointment_date.as_deref(), Some("2024-10-03"));
+        assert_eq!(appointments[1].shift_code.as_deref(), Some("AFTERNOON"));
+        assert_eq!(slot_ids(&appointments[1]), ["slot-003"]);
+
+        // First run, every matched slot opened
+        let changes = diff_appointments(&[], &appointments);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|change| change.change_type == ChangeType::Opened));
+        assert_eq!(slot_ids(&changes[0].appointment), ["slot-001"]);
+        assert_eq!(slot_ids(&changes[1].appointment), ["slot-003"]);
+
+        // Same schedule on the next run, nothing to notify
+        assert!(diff_appointments(&appointments, &appointments).is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn fixture_days_outside_the_window_are_skipped() {
+        let med_service = mock_med_service().await;
+        let mut doctor = fixture_doctor();
+        doctor.date_from = Some("2024-10-03".to_string());
+        let today = NaiveDate::from_ymd_opt(2024, 10, 1).unwrap();
+        let window = AppointmentWindow::from_doctor(&doctor, today).unwrap();
+
+        let mut analysis = TargetAnalysis::default();
+        let schedule = med_service.fetch_schedule(&doctor, &mut analysis, false).await.unwrap().response;
+        let appointments = med_service.match_appointments(&doctor, &schedule.days, &window);
+
+        assert_eq!(appointments.len(), 1);
+        assert_eq!(appointments[0].appointment_date.as_deref(), Some("2024-10-03"));
+    }
+
+    #[actix_rt::test]
+    async fn fixture_search_result_must_match_the_target() {
+        let med_service = mock_med_service().await;
+        let mut doctor = fixture_doctor();
+        doctor.hospital_id = "partner-002".to_string();
+
+        let mut analysis = TargetAnalysis::default();
+        let error = med_service.fetch_schedule(&doctor, &mut analysis, false).await.unwrap_err();
+        assert!(matches!(error, AppError::DoctorValidation(_)));
+    }
+
+    async fn search(med_service: &MedService, header_profile: Option<&str>) -> Result<Vec<SearchApiResponse>, AppError> {
+        med_service.search_med(
+            "Nguyen Van A".to_string(),
+            "city-hcm".to_string(),
+            "subject-001".to_string(),
+            header_profile,
+            false,
+        ).await
+    }
+
+    #[actix_rt::test]
+    async fn concurrent_misses_fetch_once() {
+        let (med_service, provider) = counting_med_service().await;
+
+        let results = join_all((0..4).map(|_| search(&med_service, None))).await;
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(provider.searches.load(Ordering::SeqCst), 1);
+
+        search(&med_service, None).await.unwrap();
+        assert_eq!(provider.searches.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_rt::test]
+    async fn cache_is_kept_per_header_profile() {
+        let (med_service, provider) = counting_med_service().await;
+
+        search(&med_service, None).await.unwrap();
+        // The default profile by name shares the entry
+        search(&med_service, Some("firefox")).await.unwrap();
+        assert_eq!(provider.searches.load(Ordering::SeqCst), 1);
+
+        search(&med_service, Some("mobile")).await.unwrap();
+        assert_eq!(provider.searches.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_rt::test]
+    async fn cached_schedules_are_not_from_upstream() {
+        let (med_service, provider) = counting_med_service().await;
+        let doctor = fixture_doctor();
+
+        let mut analysis = TargetAnalysis::default();
+        assert!(med_service.fetch_schedule(&doctor, &mut analysis, false).await.unwrap().from_upstream);
+        assert!(!med_service.fetch_schedule(&doctor, &mut analysis, false).await.unwrap().from_upstream);
+        assert!(med_service.fetch_schedule(&doctor, &mut analysis, true).await.unwrap().from_upstream);
+        assert_eq!(provider.schedules.load(Ordering::SeqCst), 2);
+    }
 }
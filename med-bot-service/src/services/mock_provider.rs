@@ -0,0 +1,96 @@
+use crate::dto::appointment_model::AppointmentApiResponse;
+use crate::dto::search_model::SearchApiResponse;
+use crate::error::AppError;
+use crate::metrics::Metrics;
+use crate::services::booking_provider::{decode_response, BookingProvider};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Serves recorded upstream responses from a fixture directory:
+/// `search_med/<search_key>.json` and `appointments/<doctor_id>.json`, each falling back to
+/// `search_med.json` and `appointments.json` for requests without their own recording.
+pub struct MockBookingProvider {
+    fixture_dir: PathBuf,
+    metrics: Arc<Metrics>,
+}
+
+impl MockBookingProvider {
+    pub fn builder(fixture_dir: &str) -> MockBookingProviderBuilder {
+        MockBookingProviderBuilder::new(fixture_dir)
+    }
+
+    /// Reads the first fixture found, and decodes it like a live response
+    fn load<T: DeserializeOwned>(&self, endpoint: &str, key: &str) -> Result<T, AppError> {
+        let candidates = [
+            self.fixture_dir.join(endpoint).join(format!("{}.json", fixture_key(key))),
+            self.fixture_dir.join(format!("{}.json", endpoint)),
+        ];
+
+        for path in &candidates {
+            match fs::read_to_string(path) {
+                Ok(raw_json) => {
+                    log::info!("Serving {} from fixture {}", endpoint, path.display());
+                    return decode_response(&self.metrics, endpoint, 200, &raw_json);
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(AppError::Internal(format!("Failed to read fixture {}: {}", path.display(), e))),
+            }
+        }
+
+        Err(AppError::NotFound(format!("Fixture for {} {}", endpoint, key)))
+    }
+}
+
+/// File name for a request key, e.g. `Nguyễn Văn A` -> `nguyễn_văn_a`
+fn fixture_key(key: &str) -> String {
+    key.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[async_trait(?Send)]
+impl BookingProvider for MockBookingProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn search_med(&self, search_key: String, _city_id: String, _subject_id: String, _header_profile: Option<&str>) -> Result<Vec<SearchApiResponse>, AppError> {
+        self.load("search_med", &search_key)
+    }
+
+    async fn get_appointments(&self, _subject_id: String, doctor_id: String, _service_id: String, _partner_id: String, _tree_id: Option<String>, _header_profile: Option<&str>) -> Result<AppointmentApiResponse, AppError> {
+        self.load("appointments", &doctor_id)
+    }
+}
+
+pub struct MockBookingProviderBuilder {
+    fixture_dir: PathBuf,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl MockBookingProviderBuilder {
+    pub fn new(fixture_dir: &str) -> MockBookingProviderBuilder {
+        MockBookingProviderBuilder {
+            fixture_dir: PathBuf::from(fixture_dir),
+            metrics: None,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> MockBookingProviderBuilder {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn build(self) -> MockBookingProvider {
+        MockBookingProvider {
+            fixture_dir: self.fixture_dir,
+            metrics: self.metrics.expect("Metrics not initialized"),
+        }
+    }
+}
@@ -1,2 +1,16 @@
 pub mod med_service;
-pub mod mail_service;
\ No newline at end of file
+pub mod mail_service;
+pub mod snapshot_service;
+pub mod notifier;
+pub mod telegram_service;
+pub mod webhook_service;
+pub mod doctor_service;
+pub mod lock_service;
+pub mod run_service;
+pub mod observation_service;
+pub mod ledger_service;
+pub mod digest_service;
+pub mod mail_template;
+pub mod booking_provider;
+pub mod http_provider;
+pub mod mock_provider;
\ No newline at end of file
@@ -0,0 +1,209 @@
+use crate::error::AppError;
+use crate::models::doctor_appointment::{AppointmentChange, AppointmentWindow};
+use crate::models::documents::{Doctor, RunContext, RunTrigger};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Availability changes of one target, delivered to every configured channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub doctor_ref_id: String,
+    pub doctor_name: String,
+    pub target: NotificationTarget,
+    pub changes: Vec<AppointmentChange>,
+    pub run: RunMetadata,
+}
+
+/// The target the changes were found for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationTarget {
+    pub target_id: Option<String>,
+    pub doctor_ref_id: String,
+    pub doctor_name: String,
+    pub subject_name: String,
+    pub service_name: String,
+    pub hospital_id: String,
+    /// Accepted appointment dates, e.g. `2024-10-01 - 2024-10-14`
+    pub window: String,
+}
+
+impl NotificationTarget {
+    pub fn new(doctor: &Doctor, window: &AppointmentWindow) -> NotificationTarget {
+        NotificationTarget {
+            target_id: doctor.id.map(|id| id.to_hex()),
+            doctor_ref_id: doctor.doctor_ref_id.clone(),
+            doctor_name: doctor.doctor_name.clone(),
+            subject_name: doctor.subject_name.clone(),
+            service_name: doctor.service_name.clone(),
+            hospital_id: doctor.hospital_id.clone(),
+            window: window.label(),
+        }
+    }
+}
+
+/// When, in which run and by which build the changes were found
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunMetadata {
+    /// Id of the run record, unset for digests which gather several runs
+    pub run_id: Option<String>,
+    pub trigger: Option<RunTrigger>,
+    pub checked_at: DateTime<Utc>,
+    pub year: i32,
+    pub version: String,
+}
+
+impl RunMetadata {
+    pub fn now() -> RunMetadata {
+        let checked_at = Utc::now();
+        RunMetadata {
+            run_id: None,
+            trigger: None,
+            checked_at,
+            year: checked_at.year(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    pub fn for_run(run: &RunContext) -> RunMetadata {
+        RunMetadata {
+            run_id: Some(run.id.to_hex()),
+            trigger: Some(run.trigger),
+            ..RunMetadata::now()
+        }
+    }
+}
+
+#[async_trait(?Send)]
+pub trait Notifier: Send + Sync {
+    /// Channel name used in logs, e.g. `mail` or `telegram`
+    fn name(&self) -> &str;
+
+    /// Who the channel delivers to, recorded in the notification ledger
+    fn recipient(&self) -> String;
+
+    async fn notify(&self, notification: &Notification) -> Result<(), AppError>;
+}
+
+/// Sample notification and a local HTTP stub for the channel tests
+#[cfg(test)]
+pub mod test_support {
+    use super::*;
+    use crate::dto::appointment_model::TimeSlot;
+    use crate::models::doctor_appointment::{AppointmentPicking, ChangeType};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    pub fn sample_notification() -> Notification {
+        let slot = TimeSlot {
+            time_id: "slot-001".to_string(),
+            available_slot: Some(2),
+            max_slot: Some(4),
+            start_time: "07:30".to_string(),
+            end_time: "08:00".to_string(),
+            room_id: "room-001".to_string(),
+            priority_room: 0,
+        };
+
+        Notification {
+            doctor_ref_id: "doctor-001".to_string(),
+            doctor_name: "Nguyen Van A <ENT>".to_string(),
+            target: NotificationTarget {
+                target_id: Some("66f0c0ffee0000000000abcd".to_string()),
+                doctor_ref_id: "doctor-001".to_string(),
+                doctor_name: "Nguyen Van A <ENT>".to_string(),
+                subject_name: "Tai Mui Hong".to_string(),
+                service_name: "Kham dich vu".to_string(),
+                hospital_id: "partner-001".to_string(),
+                window: "2024-10-01 - 2024-10-14".to_string(),
+            },
+            changes: vec![AppointmentChange {
+                change_type: ChangeType::Opened,
+                appointment: AppointmentPicking {
+                    doctor_name: Some("Nguyen Van A <ENT>".to_string()),
+                    appointment_day: Some("Wednesday".to_string()),
+                    appointment_date: Some("2024-10-02".to_string()),
+                    shift_code: Some("MORNING".to_string()),
+                    shift_name: Some("Buoi sang".to_string()),
+                    available_slot: Some(vec![slot]),
+                    doctor_change_info: None,
+                },
+            }],
+            run: RunMetadata::for_run(&RunContext::new(RunTrigger::Cron)),
+        }
+    }
+
+    /// Request received by the stub, with lowercase header names
+    #[derive(Debug)]
+    pub struct StubRequest {
+        pub request_line: String,
+        pub headers: Vec<(String, String)>,
+        pub body: String,
+    }
+
+    impl StubRequest {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Answers the next `requests` requests with `status`, returns the base URL and the requests received
+    pub fn serve(status: u16, requests: usize) -> (String, Receiver<StubRequest>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let request = read_request(&mut stream);
+                let response = format!("HTTP/1.1 {} Stub\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}", status);
+                stream.write_all(response.as_bytes()).unwrap();
+                sender.send(request).unwrap();
+            }
+        });
+
+        (base_url, receiver)
+    }
+
+    fn read_request(stream: &mut impl Read) -> StubRequest {
+        let mut raw = Vec::new();
+        let mut buffer = [0u8; 4096];
+        let head_end = loop {
+            let read = stream.read(&mut buffer).unwrap();
+            assert!(read > 0, "Connection closed before the request headers ended");
+            raw.extend_from_slice(&buffer[..read]);
+            if let Some(position) = raw.windows(4).position(|window| window == b"\r\n\r\n") {
+                break position;
+            }
+        };
+
+        let head = String::from_utf8_lossy(&raw[..head_end]).to_string();
+        let mut lines = head.lines();
+        let request_line = lines.next().unwrap_or_default().to_string();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect::<Vec<(String, String)>>();
+
+        let content_length = headers.iter()
+            .find(|(name, _)| name == "content-length")
+            .and_then(|(_, value)| value.parse::<usize>().ok())
+            .unwrap_or(0);
+        while raw.len() < head_end + 4 + content_length {
+            let read = stream.read(&mut buffer).unwrap();
+            assert!(read > 0, "Connection closed before the request body ended");
+            raw.extend_from_slice(&buffer[..read]);
+        }
+
+        StubRequest {
+            request_line,
+            headers,
+            body: String::from_utf8_lossy(&raw[head_end + 4..head_end + 4 + content_length]).to_string(),
+        }
+    }
+}
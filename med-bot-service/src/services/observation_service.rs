@@ -0,0 +1,410 @@
+use crate::dto::appointment_model::Day;
+use crate::dto::observation_model::{AvailabilityBucket, AvailabilityGroup, ObservationQuery, ReleaseBucket, ReleasePatternResponse};
+use crate::error::AppError;
+use crate::models::documents::{Doctor, SlotObservation};
+use crate::repositories::observation_repository::MongoObservationRepository;
+use chrono::{Datelike, NaiveDate, TimeZone, Utc, Weekday};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, Bson, DateTime, Document};
+use mongodb::error::ErrorKind;
+use mongodb::Collection;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const UNKNOWN_TIMEZONE_CODE: i32 = 40485;
+
+#[derive(Debug, Clone)]
+pub struct ObservationService {
+    mongo_observation_repository: MongoObservationRepository,
+}
+
+impl ObservationService {
+    pub fn builder(collection: Collection<SlotObservation>) -> ObservationServiceBuilder {
+        ObservationServiceBuilder::new(collection)
+    }
+
+    pub async fn ensure_indexes(&self, retention: Duration) -> Result<(), AppError> {
+        self.mongo_observation_repository.ensure_indexes(retention).await?;
+        Ok(())
+    }
+
+    /// Stores every time slot of every day and shift returned upstream, matching the target window or not
+    pub async fn record_observations(&self, doctor: &Doctor, days: &[Day]) -> Result<usize, AppError> {
+        let target_id = doctor.id
+            .ok_or_else(|| AppError::DoctorValidation(format!("Target {} has no id", doctor.doctor_name)))?;
+        let observations = slot_observations(target_id, doctor, days, DateTime::now());
+
+        if !observations.is_empty() {
+            self.mongo_observation_repository.insert_observations(&observations).await?;
+        }
+        Ok(observations.len())
+    }
+
+    pub async fn availability(&self, target_id: ObjectId, group_by: AvailabilityGroup, query: &ObservationQuery) -> Result<Vec<AvailabilityBucket>, AppError> {
+        let filter = observation_filter(target_id, query)?;
+        let timezone = query.timezone()?;
+        let key = match group_by {
+            AvailabilityGroup::Weekday => Bson::from("$appointment_weekday"),
+            AvailabilityGroup::ShiftCode => Bson::from("$shift_code"),
+            AvailabilityGroup::Hour => Bson::from(doc! {
+                "$hour": {
+                    "date": "$observed_at",
+                    "timezone": &timezone,
+                }
+            }),
+        };
+        let pipeline = vec![
+            doc! {"$match": filter},
+            doc! {
+                "$group": {
+                    "_id": key,
+                    "observations": {"$sum": 1},
+                    "open_observations": {"$sum": {"$cond": [{"$gt": ["$available_slot", 0]}, 1, 0]}},
+                    "avg_available_slot": {"$avg": "$available_slot"},
+                    "max_available_slot": {"$max": "$available_slot"},
+                }
+            },
+        ];
+
+        let mut buckets = self.mongo_observation_repository
+            .aggregate(pipeline)
+            .await
+            .map_err(|e| timezone_error(e, &timezone))?
+            .iter()
+            .map(bucket)
+            .collect::<Vec<AvailabilityBucket>>();
+        buckets.sort_by_key(|bucket| bucket_order(group_by, &bucket.key));
+        Ok(buckets)
+    }
+
+    /// When slots of the target usually open, how far ahead of the appointment and for how long
+    pub async fn release_pattern(&self, target_id: ObjectId, query: &ObservationQuery) -> Result<ReleasePatternResponse, AppError> {
+        let filter = observation_filter(target_id, query)?;
+        let timezone = query.timezone()?;
+        let mut response = ReleasePatternResponse {
+            target_id: target_id.to_hex(),
+            timezone: timezone.clone(),
+            observed_from: None,
+            observed_to: None,
+            releases: 0,
+            by_weekday: vec![],
+            by_hour: vec![],
+            usual_weekday: None,
+            usual_hour: None,
+            median_lead_days: None,
+            filled: 0,
+            median_open_minutes: None,
+            summary: None,
+        };
+
+        let bounds = self.mongo_observation_repository
+            .aggregate(vec![
+                doc! {"$match": filter.clone()},
+                doc! {"$group": {"_id": null, "first": {"$min": "$observed_at"}, "last": {"$max": "$observed_at"}}},
+            ])
+            .await?;
+        let Some((first_observed_at, last_observed_at)) = bounds.first()
+            .and_then(|bounds| Some((bounds.get_datetime("first").ok()?, bounds.get_datetime("last").ok()?))) else {
+            return Ok(response);
+        };
+        response.observed_from = Some(utc(*first_observed_at));
+        response.observed_to = Some(utc(*last_observed_at));
+
+        // Per slot: first and last time it was seen with free places, and last time it was seen at all.
+        // Slots already open at the first observation were released before watching started.
+        let slots = self.mongo_observation_repository
+            .aggregate(vec![
+                doc! {"$match": filter},
+                doc! {
+                    "$group": {
+                        "_id": {"date": "$appointment_date", "shift_code": "$shift_code", "time_id": "$time_id"},
+                        "first_open_at": {"$min": {"$cond": [{"$gt": ["$available_slot", 0]}, "$observed_at", null]}},
+                        "last_open_at": {"$max": {"$cond": [{"$gt": ["$available_slot", 0]}, "$observed_at", null]}},
+                        "last_seen_at": {"$max": "$observed_at"},
+                    }
+                },
+                doc! {"$match": {"first_open_at": {"$gt": first_observed_at}}},
+                doc! {
+                    "$project": {
+                        "appointment_date": "$_id.date",
+                        "first_open_at": 1,
+                        "last_open_at": 1,
+                        "last_seen_at": 1,
+                        "release_date": {"$dateToString": {"format": "%Y-%m-%d", "date": "$first_open_at", "timezone": &timezone}},
+                        "weekday": {"$dayOfWeek": {"date": "$first_open_at", "timezone": &timezone}},
+                        "hour": {"$hour": {"date": "$first_open_at", "timezone": &timezone}},
+                    }
+                },
+            ])
+            .await
+            .map_err(|e| timezone_error(e, &timezone))?;
+
+        let mut by_weekday: BTreeMap<u32, u64> = BTreeMap::new();
+        let mut by_hour: BTreeMap<u32, u64> = BTreeMap::new();
+        let mut lead_days = vec![];
+        let mut open_minutes = vec![];
+        for slot in &slots {
+            let (Ok(first_open_at), Ok(last_open_at), Ok(last_seen_at)) = (
+                slot.get_datetime("first_open_at"),
+                slot.get_datetime("last_open_at"),
+                slot.get_datetime("last_seen_at"),
+            ) else {
+                continue;
+            };
+            response.releases += 1;
+
+            if let Some(weekday) = slot.get_i32("weekday").ok().and_then(mongo_weekday) {
+                *by_weekday.entry(weekday.num_days_from_monday()).or_default() += 1;
+            }
+            if let Ok(hour) = slot.get_i32("hour") {
+                *by_hour.entry(hour as u32).or_default() += 1;
+            }
+            // Both days in the requested timezone, a release late in the evening counts for that day
+            if let (Some(appointment_date), Some(release_date)) = (parse_day(slot, "appointment_date"), parse_day(slot, "release_date")) {
+                lead_days.push((appointment_date - release_date).num_days());
+            }
+            if last_seen_at > last_open_at {
+                response.filled += 1;
+                open_minutes.push((last_open_at.timestamp_millis() - first_open_at.timestamp_millis()) / 60_000);
+            }
+        }
+
+        response.usual_weekday = most_common(&by_weekday).and_then(weekday_name);
+        response.usual_hour = most_common(&by_hour);
+        response.median_lead_days = median(&mut lead_days);
+        response.median_open_minutes = median(&mut open_minutes);
+        response.summary = match (&response.usual_weekday, response.usual_hour, response.median_lead_days) {
+            (Some(weekday), Some(hour), Some(lead_days)) => Some(format!(
+                "usually {} at {:02}:00, about {} days ahead", weekday, hour, lead_days
+            )),
+            _ => None,
+        };
+        response.by_weekday = by_weekday.into_iter()
+            .filter_map(|(day, releases)| Some(ReleaseBucket { key: weekday_name(day)?, releases }))
+            .collect();
+        response.by_hour = by_hour.into_iter()
+            .map(|(hour, releases)| ReleaseBucket { key: hour.to_string(), releases })
+            .collect();
+        Ok(response)
+    }
+}
+
+fn observation_filter(target_id: ObjectId, query: &ObservationQuery) -> Result<Document, AppError> {
+    let mut filter = doc! {"target_id": target_id};
+    let mut observed_at = Document::new();
+    if let Some(from) = &query.from {
+        observed_at.insert("$gte", day_start(from)?);
+    }
+    if let Some(to) = &query.to {
+        let to = day_start(to)?;
+        observed_at.insert("$lt", DateTime::from_millis(to.timestamp_millis() + 24 * 60 * 60 * 1000));
+    }
+    if !observed_at.is_empty() {
+        filter.insert("observed_at", observed_at);
+    }
+    Ok(filter)
+}
+
+/// One observation per time slot of every day and shift, days without a date are skipped
+fn slot_observations(target_id: ObjectId, doctor: &Doctor, days: &[Day], observed_at: DateTime) -> Vec<SlotObservation> {
+    days.iter()
+        .filter_map(|day| Some((day.day_date()?, day)))
+        .flat_map(|(date, day)| day.shifts.iter().map(move |shift| (date, shift)))
+        .flat_map(|(date, shift)| {
+            shift.time_slot_in_day.iter().flatten().map(move |slot| SlotObservation {
+                id: None,
+                target_id,
+                doctor_ref_id: doctor.doctor_ref_id.clone(),
+                observed_at,
+                appointment_date: date.format("%Y-%m-%d").to_string(),
+                appointment_weekday: date.weekday().to_string(),
+                shift_code: shift.shift_code.clone(),
+                shift_name: shift.shift_name.clone(),
+                time_id: slot.time_id.clone(),
+                start_time: slot.start_time.clone(),
+                end_time: slot.end_time.clone(),
+                available_slot: slot.available_slot,
+                max_slot: slot.max_slot,
+            })
+        })
+        .collect()
+}
+
+/// Mongo rejects an Olson name it doesn't know only when running the pipeline
+fn timezone_error(error: mongodb::error::Error, timezone: &str) -> AppError {
+    match error.kind.as_ref() {
+        ErrorKind::Command(command_error) if command_error.code == UNKNOWN_TIMEZONE_CODE => {
+            AppError::BadRequest(format!("Unknown timezone {}", timezone))
+        }
+        _ => AppError::Mongo(error),
+    }
+}
+
+/// `$dayOfWeek` counts from 1 = Sunday to 7 = Saturday
+fn mongo_weekday(day: i32) -> Option<Weekday> {
+    if !(1..=7).contains(&day) {
+        return None;
+    }
+    Weekday::try_from(((day + 5) % 7) as u8).ok()
+}
+
+fn parse_day(document: &Document, field: &str) -> Option<NaiveDate> {
+    document.get_str(field).ok()
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+}
+
+fn most_common(counts: &BTreeMap<u32, u64>) -> Option<u32> {
+    counts.iter()
+        .max_by_key(|(_, count)| **count)
+        .map(|(key, _)| *key)
+}
+
+fn median(values: &mut [i64]) -> Option<i64> {
+    values.sort_unstable();
+    values.get(values.len() / 2).copied()
+}
+
+fn weekday_name(days_from_monday: u32) -> Option<String> {
+    Weekday::try_from(days_from_monday as u8).ok().map(|weekday| weekday.to_string())
+}
+
+fn utc(datetime: DateTime) -> chrono::DateTime<Utc> {
+    chrono::DateTime::<Utc>::from(datetime.to_system_time())
+}
+
+fn day_start(date: &str) -> Result<DateTime, AppError> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest(format!("Invalid date {}, expected YYYY-MM-DD", date)))?;
+    let start = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default());
+    Ok(DateTime::from_millis(start.timestamp_millis()))
+}
+
+fn bucket(group: &Document) -> AvailabilityBucket {
+    let number = |field: &str| match group.get(field) {
+        Some(Bson::Int32(value)) => Some(*value as i64),
+        Some(Bson::Int64(value)) => Some(*value),
+        Some(Bson::Double(value)) => Some(*value as i64),
+        _ => None,
+    };
+
+    AvailabilityBucket {
+        key: match group.get("_id") {
+            Some(Bson::String(key)) => key.clone(),
+            Some(Bson::Int32(key)) => key.to_string(),
+            Some(Bson::Int64(key)) => key.to_string(),
+            _ => "unknown".to_string(),
+        },
+        observations: number("observations").unwrap_or(0) as u64,
+        open_observations: number("open_observations").unwrap_or(0) as u64,
+        avg_available_slot: group.get_f64("avg_available_slot").ok(),
+        max_available_slot: number("max_available_slot"),
+    }
+}
+
+/// Weekdays from Monday and hours numerically, other keys alphabetically
+fn bucket_order(group_by: AvailabilityGroup, key: &str) -> (u32, String) {
+    let position = match group_by {
+        AvailabilityGroup::Weekday => key.parse::<Weekday>().map(|day| day.num_days_from_monday()).ok(),
+        AvailabilityGroup::Hour => key.parse::<u32>().ok(),
+        AvailabilityGroup::ShiftCode => None,
+    };
+    (position.unwrap_or(u32::MAX), key.to_string())
+}
+
+
+pub struct ObservationServiceBuilder {
+    mongo_observation_repository: MongoObservationRepository,
+}
+
+impl ObservationServiceBuilder {
+    pub fn new(collection: Collection<SlotObservation>) -> ObservationServiceBuilder {
+        ObservationServiceBuilder {
+            mongo_observation_repository: MongoObservationRepository::builder(collection).build(),
+        }
+    }
+
+    pub fn build(self) -> ObservationService {
+        ObservationService {
+            mongo_observation_repository: self.mongo_observation_repository,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::appointment_model::AppointmentApiResponse;
+
+    #[test]
+    fn mongo_weekdays_start_on_sunday() {
+        let weekdays = (1..=7).map(mongo_weekday).collect::<Vec<Option<Weekday>>>();
+
+        assert_eq!(weekdays, vec![
+            Some(Weekday::Sun),
+            Some(Weekday::Mon),
+            Some(Weekday::Tue),
+            Some(Weekday::Wed),
+            Some(Weekday::Thu),
+            Some(Weekday::Fri),
+            Some(Weekday::Sat),
+        ]);
+        assert_eq!(mongo_weekday(0), None);
+        assert_eq!(mongo_weekday(8), None);
+    }
+
+    #[test]
+    fn median_of_unsorted_values() {
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median(&mut [7]), Some(7));
+        assert_eq!(median(&mut [14, 3, 9]), Some(9));
+        // Even counts take the upper middle value
+        assert_eq!(median(&mut [10, 1, 4, 2]), Some(4));
+    }
+
+    #[test]
+    fn most_common_prefers_the_highest_count() {
+        assert_eq!(most_common(&BTreeMap::new()), None);
+        assert_eq!(most_common(&BTreeMap::from([(0, 2), (3, 5), (6, 1)])), Some(3));
+    }
+
+    #[test]
+    fn most_common_tie_goes_to_the_last_key() {
+        assert_eq!(most_common(&BTreeMap::from([(1, 4), (5, 4)])), Some(5));
+    }
+
+    #[test]
+    fn days_are_read_from_pipeline_fields() {
+        let slot = doc! {"appointment_date": "2024-10-15", "release_date": "2024-10-01", "bad": "15/10/2024"};
+
+        assert_eq!(parse_day(&slot, "appointment_date"), NaiveDate::from_ymd_opt(2024, 10, 15));
+        assert_eq!(parse_day(&slot, "release_date"), NaiveDate::from_ymd_opt(2024, 10, 1));
+        assert_eq!(parse_day(&slot, "bad"), None);
+        assert_eq!(parse_day(&slot, "missing"), None);
+    }
+
+    #[test]
+    fn observations_fall_back_to_timemiliseconds() {
+        let mut response = serde_json::from_str::<AppointmentApiResponse>(include_str!("../../fixtures/appointments.json")).unwrap();
+        response.days[0].date = None;
+        response.days[1].date = None;
+        response.days[1].timemiliseconds = None;
+        let doctor = serde_json::from_value::<Doctor>(serde_json::json!({
+            "doctor_ref_id": "doctor-001",
+            "doctor_name": "Nguyen Van A",
+            "subject_ref_id": "subject-001",
+            "subject_name": "tai mui hong",
+            "service_name": "kham dich vu",
+            "hospital_id": "partner-001",
+            "city_id": "city-hcm",
+            "current_target": true,
+            "active": true,
+        })).unwrap();
+
+        let observations = slot_observations(ObjectId::new(), &doctor, &response.days, DateTime::now());
+
+        assert!(!observations.is_empty());
+        // The second day has no timestamp at all and is skipped
+        assert!(observations.iter().all(|observation| observation.appointment_date == "2024-10-02"));
+        assert_eq!(observations[0].appointment_weekday, "Wed");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::dto::run_model::RunQuery;
+use crate::error::AppError;
+use crate::models::doctor_appointment::TargetAnalysis;
+use crate::models::documents::{RunContext, RunOutcome, RunRecord, RunTargetRecord};
+use crate::repositories::run_repository::MongoRunRepository;
+use mongodb::bson::{to_bson, DateTime, Document};
+use mongodb::Collection;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RunService {
+    mongo_run_repository: MongoRunRepository,
+}
+
+impl RunService {
+    pub fn builder(collection: Collection<RunRecord>) -> RunServiceBuilder {
+        RunServiceBuilder::new(collection)
+    }
+
+    pub async fn ensure_indexes(&self, retention: Duration) -> Result<(), AppError> {
+        self.mongo_run_repository.ensure_indexes(retention).await?;
+        Ok(())
+    }
+
+    /// Stores what an analysis did. Failing to store it is logged only, it never fails the run itself.
+    pub async fn record_run(
+        &self,
+        context: RunContext,
+        started_at: DateTime,
+        analysis: &Result<Vec<TargetAnalysis>, AppError>,
+    ) -> RunRecord {
+        let run = build_run(context, started_at, analysis);
+
+        if let Err(e) = self.mongo_run_repository.insert_run(&run).await {
+            log::error!("Failed to store {:?} run record {}: {}", context.trigger, context.id, e);
+        }
+        run
+    }
+
+    /// One page of runs, newest first, with the total number of matching runs
+    pub async fn list_runs(&self, query: &RunQuery) -> Result<(Vec<RunRecord>, u64), AppError> {
+        let mut filter = Document::new();
+        if let Some(target_id) = query.target_id()? {
+            filter.insert("targets.target_id", target_id);
+        }
+        if let Some(outcome) = query.outcome {
+            filter.insert("outcome", to_bson(&outcome).map_err(|e| AppError::Internal(e.to_string()))?);
+        }
+
+        let skip = query.skip()?;
+        let runs = self.mongo_run_repository
+            .find_runs(filter.clone(), skip, query.page_size() as i64)
+            .await?;
+        let total = self.mongo_run_repository.count_runs(filter).await?;
+        Ok((runs, total))
+    }
+}
+
+fn build_run(context: RunContext, started_at: DateTime, analysis: &Result<Vec<TargetAnalysis>, AppError>) -> RunRecord {
+    let (targets, outcome, error) = match analysis {
+        Ok(results) => {
+            let (outcome, error) = summarize(results);
+            (results.iter().map(target_record).collect::<Vec<RunTargetRecord>>(), outcome, error)
+        }
+        Err(e) => (vec![], RunOutcome::Failed, Some(e.to_string())),
+    };
+
+    RunRecord {
+        id: Some(context.id),
+        trigger: context.trigger,
+        started_at,
+        finished_at: DateTime::now(),
+        outcome,
+        slots_found: targets.iter().map(|target| target.slots_found).sum(),
+        notifications_sent: targets.iter().map(|target| target.notifications_sent).sum(),
+        targets,
+        error,
+    }
+}
+
+fn target_record(result: &TargetAnalysis) -> RunTargetRecord {
+    RunTargetRecord {
+        target_id: result.target_id,
+        doctor_ref_id: result.doctor_ref_id.clone(),
+        doctor_name: result.doctor_name.clone(),
+        upstream_latency_ms: result.upstream_latency_ms,
+        slots_found: result.slots_found() as u64,
+        changes: result.changes.len() as u64,
+        notifications_sent: result.notifications_sent as u64,
+        error: result.error.clone(),
+        error_code: result.error_code.clone(),
+    }
+}
+
+fn summarize(results: &[TargetAnalysis]) -> (RunOutcome, Option<String>) {
+    let errors = results.iter()
+        .filter_map(|result| result.error.as_ref().map(|e| format!("{}: {}", result.doctor_name, e)))
+        .collect::<Vec<String>>();
+
+    if errors.is_empty() {
+        (RunOutcome::Success, None)
+    } else if errors.len() == results.len() {
+        (RunOutcome::Failed, Some(errors.join("; ")))
+    } else {
+        (RunOutcome::Partial, Some(errors.join("; ")))
+    }
+}
+
+
+pub struct RunServiceBuilder {
+    mongo_run_repository: MongoRunRepository,
+}
+
+impl RunServiceBuilder {
+    pub fn new(collection: Collection<RunRecord>) -> RunServiceBuilder {
+        RunServiceBuilder {
+            mongo_run_repository: MongoRunRepository::builder(collection).build(),
+        }
+    }
+
+    pub fn build(self) -> RunService {
+        RunService {
+            mongo_run_repository: self.mongo_run_repository,
+        }
+    }
+}
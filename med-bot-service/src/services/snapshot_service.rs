@@ -0,0 +1,134 @@
+use crate::dto::appointment_model::TimeSlot;
+use crate::error::AppError;
+use crate::models::doctor_appointment::{AppointmentChange, AppointmentPicking, ChangeType};
+use crate::models::documents::{Doctor, SlotSnapshot};
+use crate::repositories::snapshot_repository::MongoSnapshotRepository;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use mongodb::Collection;
+use std::collections::BTreeMap;
+
+/// Slot identity across runs: appointment date, shift code and `TimeSlot.time_id`
+type SlotKey = (String, Option<String>, String);
+
+#[derive(Debug, Clone)]
+pub struct SnapshotService {
+    mongo_snapshot_repository: MongoSnapshotRepository,
+}
+
+impl SnapshotService {
+    pub fn builder(collection: Collection<SlotSnapshot>) -> SnapshotServiceBuilder {
+        SnapshotServiceBuilder::new(collection)
+    }
+
+    pub async fn ensure_indexes(&self) -> Result<(), AppError> {
+        self.mongo_snapshot_repository.ensure_indexes().await?;
+        Ok(())
+    }
+
+    /// Compares the slots found in this run with the latest stored snapshot of the target
+    pub async fn diff_with_previous(&self, doctor: &Doctor, appointments: &[AppointmentPicking]) -> Result<Vec<AppointmentChange>, AppError> {
+        let previous = self.mongo_snapshot_repository
+            .get_latest_snapshot(Self::target_id(doctor)?)
+            .await?
+            .map(|snapshot| snapshot.appointments)
+            .unwrap_or_default();
+
+        Ok(diff_appointments(&previous, appointments))
+    }
+
+    pub async fn save_snapshot(&self, doctor: &Doctor, appointments: &[AppointmentPicking]) -> Result<(), AppError> {
+        let snapshot = SlotSnapshot {
+            id: None,
+            target_id: Self::target_id(doctor)?,
+            doctor_ref_id: doctor.doctor_ref_id.clone(),
+            taken_at: DateTime::now(),
+            appointments: appointments.to_vec(),
+        };
+
+        self.mongo_snapshot_repository.upsert_snapshot(snapshot).await?;
+        Ok(())
+    }
+
+    fn target_id(doctor: &Doctor) -> Result<ObjectId, AppError> {
+        doctor.id.ok_or_else(|| AppError::DoctorValidation(format!("Target {} has no id", doctor.doctor_name)))
+    }
+}
+
+pub(crate) fn diff_appointments(previous: &[AppointmentPicking], current: &[AppointmentPicking]) -> Vec<AppointmentChange> {
+    let previous_slots = index_slots(previous);
+    let current_slots = index_slots(current);
+
+    let mut grouped: BTreeMap<(ChangeType, String, Option<String>), AppointmentChange> = BTreeMap::new();
+    let mut push_change = |change_type: ChangeType, appointment: &AppointmentPicking, slot: &TimeSlot| {
+        let key = (
+            change_type,
+            appointment.appointment_date.clone().unwrap_or_default(),
+            appointment.shift_code.clone(),
+        );
+        grouped.entry(key)
+            .or_insert_with(|| AppointmentChange {
+                change_type,
+                appointment: AppointmentPicking {
+                    available_slot: None,
+                    ..appointment.clone()
+                },
+            })
+            .appointment
+            .available_slot
+            .get_or_insert_with(Vec::new)
+            .push(slot.clone());
+    };
+
+    for (key, (appointment, slot)) in &current_slots {
+        match previous_slots.get(key) {
+            None => push_change(ChangeType::Opened, appointment, slot),
+            Some((_, previous_slot)) if previous_slot.available_slot != slot.available_slot => {
+                push_change(ChangeType::CountChanged, appointment, slot)
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (key, (appointment, slot)) in &previous_slots {
+        if !current_slots.contains_key(key) {
+            push_change(ChangeType::Closed, appointment, slot);
+        }
+    }
+
+    grouped.into_values().collect()
+}
+
+fn index_slots(appointments: &[AppointmentPicking]) -> BTreeMap<SlotKey, (&AppointmentPicking, &TimeSlot)> {
+    appointments.iter()
+        .flat_map(|appointment| {
+            appointment.available_slot.iter().flatten().map(move |slot| {
+                let key = (
+                    appointment.appointment_date.clone().unwrap_or_default(),
+                    appointment.shift_code.clone(),
+                    slot.time_id.clone(),
+                );
+                (key, (appointment, slot))
+            })
+        })
+        .collect()
+}
+
+pub struct SnapshotServiceBuilder {
+    mongo_snapshot_repository: MongoSnapshotRepository,
+}
+
+impl SnapshotServiceBuilder {
+    pub fn new(collection: Collection<SlotSnapshot>) -> SnapshotServiceBuilder {
+        let mongo_snapshot_repository = MongoSnapshotRepository::builder(collection).build();
+        SnapshotServiceBuilder {
+            mongo_snapshot_repository,
+        }
+    }
+
+    pub fn build(self) -> SnapshotService {
+        SnapshotService {
+            mongo_snapshot_repository: self.mongo_snapshot_repository,
+        }
+    }
+}
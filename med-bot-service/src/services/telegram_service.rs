@@ -0,0 +1,162 @@
+use crate::config::telegram_config::TelegramClient;
+use crate::error::AppError;
+use crate::services::notifier::{Notification, Notifier};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+
+#[derive(Debug, Clone)]
+pub struct TelegramService {
+    client: Client,
+    telegram_client: TelegramClient,
+}
+
+impl TelegramService {
+    pub fn builder(client: Client, telegram_client: TelegramClient) -> TelegramServiceBuilder {
+        TelegramServiceBuilder::new(client, telegram_client)
+    }
+
+    /// Errors leave the request URL out, it carries the bot token
+    pub async fn send_message(&self, chat_id: &str, text: &str) -> Result<(), reqwest::Error> {
+        let url = format!("{}/bot{}/sendMessage", self.telegram_client.api_url, self.telegram_client.bot_token);
+
+        self.client.post(url)
+            .json(&json!({
+                "chat_id": chat_id,
+                "text": text,
+                "parse_mode": "HTML",
+                "disable_web_page_preview": true,
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(reqwest::Error::without_url)?;
+
+        Ok(())
+    }
+
+    fn format_message(notification: &Notification) -> String {
+        let mut lines = vec![format!("<b>{}</b>", escape_html(&notification.doctor_name))];
+
+        for change in &notification.changes {
+            let appointment = &change.appointment;
+            lines.push(String::new());
+            lines.push(format!(
+                "<b>{}</b>: {} {}",
+                change.change_type.label(),
+                escape_html(&appointment.appointment_date.clone().unwrap_or_default()),
+                escape_html(&appointment.shift_name.clone()
+                    .or(appointment.shift_code.clone())
+                    .unwrap_or_default()),
+            ));
+
+            for slot in appointment.available_slot.iter().flatten() {
+                lines.push(format!(
+                    "{} - {}: {}/{} available",
+                    escape_html(&slot.start_time),
+                    escape_html(&slot.end_time),
+                    slot.available_slot.unwrap_or(0),
+                    slot.max_slot.unwrap_or(0),
+                ));
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for TelegramService {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    fn recipient(&self) -> String {
+        self.telegram_client.chat_ids.join(";")
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), AppError> {
+        let text = Self::format_message(notification);
+
+        for chat_id in &self.telegram_client.chat_ids {
+            self.send_message(chat_id, &text).await
+                .map_err(|e| AppError::Notifier(format!("telegram chat {}: {}", chat_id, e)))?;
+            log::info!("Telegram message sent to chat {}", chat_id);
+        }
+
+        Ok(())
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub struct TelegramServiceBuilder {
+    client: Client,
+    telegram_client: TelegramClient,
+}
+
+impl TelegramServiceBuilder {
+    pub fn new(client: Client, telegram_client: TelegramClient) -> TelegramServiceBuilder {
+        TelegramServiceBuilder { client, telegram_client }
+    }
+
+    pub fn build(self) -> TelegramService {
+        TelegramService {
+            client: self.client,
+            telegram_client: self.telegram_client,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::notifier::test_support::{sample_notification, serve};
+
+    fn telegram_service(api_url: String, chat_ids: &[&str]) -> TelegramService {
+        let telegram_client = TelegramClient {
+            api_url,
+            bot_token: "123:secret".to_string(),
+            chat_ids: chat_ids.iter().map(|chat_id| chat_id.to_string()).collect(),
+        };
+        TelegramService::builder(Client::new(), telegram_client).build()
+    }
+
+    #[actix_rt::test]
+    async fn sends_the_message_to_every_chat() {
+        let (api_url, requests) = serve(200, 2);
+        let service = telegram_service(api_url, &["1001", "1002"]);
+
+        service.notify(&sample_notification()).await.unwrap();
+
+        for chat_id in ["1001", "1002"] {
+            let request = requests.recv().unwrap();
+            assert_eq!(request.request_line, "POST /bot123:secret/sendMessage HTTP/1.1");
+            let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+            assert_eq!(body["chat_id"], chat_id);
+            assert_eq!(body["parse_mode"], "HTML");
+            let text = body["text"].as_str().unwrap();
+            assert!(text.starts_with("<b>Nguyen Van A &lt;ENT&gt;</b>"), "{}", text);
+            assert!(text.contains("<b>New slots opened</b>: 2024-10-02 Buoi sang"), "{}", text);
+            assert!(text.contains("07:30 - 08:00: 2/4 available"), "{}", text);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn rejected_message_fails_the_channel() {
+        let (api_url, requests) = serve(403, 1);
+        let service = telegram_service(api_url, &["1001", "1002"]);
+
+        let result = service.notify(&sample_notification()).await;
+
+        let Err(AppError::Notifier(message)) = result else { panic!("expected a notifier error") };
+        assert!(message.starts_with("telegram chat 1001"), "{}", message);
+        assert!(message.contains("403"), "{}", message);
+        assert!(!message.contains("secret"), "bot token leaked: {}", message);
+        assert_eq!(requests.iter().count(), 1);
+    }
+}
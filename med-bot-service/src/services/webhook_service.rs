@@ -0,0 +1,111 @@
+use crate::config::webhook_config::WebhookClient;
+use crate::error::AppError;
+use crate::services::notifier::{Notification, Notifier};
+use async_trait::async_trait;
+use reqwest::Client;
+
+#[derive(Debug, Clone)]
+pub struct WebhookService {
+    client: Client,
+    webhook_client: WebhookClient,
+}
+
+impl WebhookService {
+    pub fn builder(client: Client, webhook_client: WebhookClient) -> WebhookServiceBuilder {
+        WebhookServiceBuilder::new(client, webhook_client)
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for WebhookService {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    /// The URL without its query string, which may carry credentials
+    fn recipient(&self) -> String {
+        self.webhook_client.url.split('?').next().unwrap_or_default().to_string()
+    }
+
+    /// Posts the notification as JSON to the configured URL
+    async fn notify(&self, notification: &Notification) -> Result<(), AppError> {
+        let mut request = self.client.post(self.webhook_client.url.clone())
+            .json(notification);
+
+        if let Some(auth_token) = &self.webhook_client.auth_token {
+            request = request.bearer_auth(auth_token);
+        }
+
+        request.send()
+            .await
+            .and_then(|response| response.error_for_status())
+            // The URL may carry credentials in its query string
+            .map_err(|e| AppError::Notifier(format!("webhook: {}", e.without_url())))?;
+
+        log::info!("Webhook notification sent for {}", notification.doctor_name);
+        Ok(())
+    }
+}
+
+pub struct WebhookServiceBuilder {
+    client: Client,
+    webhook_client: WebhookClient,
+}
+
+impl WebhookServiceBuilder {
+    pub fn new(client: Client, webhook_client: WebhookClient) -> WebhookServiceBuilder {
+        WebhookServiceBuilder { client, webhook_client }
+    }
+
+    pub fn build(self) -> WebhookService {
+        WebhookService {
+            client: self.client,
+            webhook_client: self.webhook_client,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::notifier::test_support::{sample_notification, serve};
+
+    fn webhook_service(url: String, auth_token: Option<&str>) -> WebhookService {
+        let webhook_client = WebhookClient {
+            url,
+            auth_token: auth_token.map(|token| token.to_string()),
+        };
+        WebhookService::builder(Client::new(), webhook_client).build()
+    }
+
+    #[actix_rt::test]
+    async fn posts_the_notification_as_json() {
+        let (base_url, requests) = serve(204, 1);
+        let service = webhook_service(format!("{}/hooks/med?key=secret", base_url), Some("token-1"));
+
+        service.notify(&sample_notification()).await.unwrap();
+
+        let request = requests.recv().unwrap();
+        assert_eq!(request.request_line, "POST /hooks/med?key=secret HTTP/1.1");
+        assert_eq!(request.header("authorization"), Some("Bearer token-1"));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        let body: Notification = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body.doctor_ref_id, "doctor-001");
+        assert_eq!(body.target.window, "2024-10-01 - 2024-10-14");
+        assert_eq!(body.changes.len(), 1);
+        assert_eq!(service.recipient(), format!("{}/hooks/med", base_url));
+    }
+
+    #[actix_rt::test]
+    async fn server_error_fails_the_channel() {
+        let (base_url, _requests) = serve(500, 1);
+        let service = webhook_service(format!("{}/hooks/med?key=secret", base_url), None);
+
+        let result = service.notify(&sample_notification()).await;
+
+        let Err(AppError::Notifier(message)) = result else { panic!("expected a notifier error") };
+        assert!(message.starts_with("webhook:"), "{}", message);
+        assert!(message.contains("500"), "{}", message);
+        assert!(!message.contains("key=secret"), "query string leaked: {}", message);
+    }
+}